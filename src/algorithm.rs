@@ -0,0 +1,204 @@
+//! Hash algorithms used to build and search the rainbow tables.
+//! The SHA-3, SHAKE and Keccak digests are computed with our implementation of Keccak (hashage module),
+//! the other digests with the RustCrypto crates.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use md4::Md4;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::hashage::{Sha3, Shake};
+
+/// A hash algorithm without salt.
+pub trait HashAlgorithm: Send + Sync {
+    /// Hash a password.
+    /// # Arguments
+    /// * `password` - The password to hash
+    /// # Returns
+    /// The digest of the password.
+    fn hash(&self, password: &[u8]) -> Vec<u8> {
+        let mut digest = vec![0; self.digest_size()];
+        self.hash_into(password, &mut digest);
+        digest
+    }
+
+    /// Hash a password into a buffer, without allocation.
+    /// # Arguments
+    /// * `password` - The password to hash
+    /// * `digest` - The buffer receiving the digest, of `digest_size()` bytes
+    fn hash_into(&self, password: &[u8], digest: &mut [u8]);
+
+    /// Get the size of a digest in bytes.
+    fn digest_size(&self) -> usize;
+}
+
+/// The hash algorithms available for the rainbow tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum HashType {
+    #[value(name = "md5")]
+    Md5,
+    #[value(name = "sha1")]
+    Sha1,
+    #[value(name = "sha256")]
+    Sha256,
+    /// MD4 of the UTF-16LE password (Windows NT hashes)
+    #[value(name = "ntlm")]
+    Ntlm,
+    #[value(name = "sha3-224")]
+    Sha3_224,
+    #[value(name = "sha3-256")]
+    Sha3_256,
+    #[value(name = "sha3-384")]
+    Sha3_384,
+    #[value(name = "sha3-512")]
+    Sha3_512,
+    /// SHAKE128 with 256 bits of output
+    #[value(name = "shake128")]
+    Shake128,
+    /// SHAKE256 with 512 bits of output
+    #[value(name = "shake256")]
+    Shake256,
+    /// Original Keccak-256, before the SHA-3 padding (Ethereum)
+    #[value(name = "keccak-256")]
+    Keccak256,
+    /// Original Keccak-512, before the SHA-3 padding
+    #[value(name = "keccak-512")]
+    Keccak512,
+}
+
+/// Hash a password with a RustCrypto hasher, the digest being computed on the stack.
+fn digest_into<D: Digest>(password: &[u8], digest: &mut [u8]) {
+    digest.copy_from_slice(&D::digest(password));
+}
+
+impl HashAlgorithm for HashType {
+    fn hash_into(&self, password: &[u8], digest: &mut [u8]) {
+        match self {
+            HashType::Md5 => digest_into::<Md5>(password, digest),
+            HashType::Sha1 => digest_into::<Sha1>(password, digest),
+            HashType::Sha256 => digest_into::<Sha256>(password, digest),
+            HashType::Ntlm => {
+                // The UTF-16LE code units are hashed one by one
+                let mut md4 = Md4::new();
+                for c in String::from_utf8_lossy(password).encode_utf16() {
+                    md4.update(c.to_le_bytes());
+                }
+                digest.copy_from_slice(&md4.finalize());
+            },
+            HashType::Sha3_224 => Sha3::new(224).hash_into(password, digest),
+            HashType::Sha3_256 => Sha3::new(256).hash_into(password, digest),
+            HashType::Sha3_384 => Sha3::new(384).hash_into(password, digest),
+            HashType::Sha3_512 => Sha3::new(512).hash_into(password, digest),
+            HashType::Shake128 => Shake::new(128).hash_into(password, digest),
+            HashType::Shake256 => Shake::new(256).hash_into(password, digest),
+            HashType::Keccak256 => Sha3::keccak(256).hash_into(password, digest),
+            HashType::Keccak512 => Sha3::keccak(512).hash_into(password, digest),
+        }
+    }
+
+    fn digest_size(&self) -> usize {
+        match self {
+            HashType::Md5 | HashType::Ntlm => 16,
+            HashType::Sha1 => 20,
+            HashType::Sha3_224 => 28,
+            HashType::Sha256 | HashType::Sha3_256 | HashType::Shake128 | HashType::Keccak256 => 32,
+            HashType::Sha3_384 => 48,
+            HashType::Sha3_512 | HashType::Shake256 | HashType::Keccak512 => 64,
+        }
+    }
+}
+
+/// Display the algorithm as in the command line and the manifest.
+impl Display for HashType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            HashType::Md5 => "md5",
+            HashType::Sha1 => "sha1",
+            HashType::Sha256 => "sha256",
+            HashType::Ntlm => "ntlm",
+            HashType::Sha3_224 => "sha3-224",
+            HashType::Sha3_256 => "sha3-256",
+            HashType::Sha3_384 => "sha3-384",
+            HashType::Sha3_512 => "sha3-512",
+            HashType::Shake128 => "shake128",
+            HashType::Shake256 => "shake256",
+            HashType::Keccak256 => "keccak-256",
+            HashType::Keccak512 => "keccak-512",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Parse the algorithm as in the command line and the manifest.
+impl FromStr for HashType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <HashType as clap::ValueEnum>::from_str(s, true).map_err(|_| format!("Unsupported hash algorithm: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Hash;
+
+    /// Check the digest of "password".
+    fn check(hash_type: HashType, expected: &str) {
+        let digest = hash_type.hash(b"password");
+        assert_eq!(digest.len(), hash_type.digest_size());
+        let mut buffer = [0xff; 64];
+        hash_type.hash_into(b"password", &mut buffer[..hash_type.digest_size()]);
+        assert_eq!(buffer[..hash_type.digest_size()], digest);
+        assert_eq!(Hash::new(digest).to_string(), expected);
+        assert_eq!(hash_type.to_string().parse::<HashType>().unwrap(), hash_type);
+    }
+
+    #[test]
+    fn test_md5() {
+        check(HashType::Md5, "5f4dcc3b5aa765d61d8327deb882cf99");
+    }
+
+    #[test]
+    fn test_sha1() {
+        check(HashType::Sha1, "5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8");
+    }
+
+    #[test]
+    fn test_sha256() {
+        check(HashType::Sha256, "5e884898da28047151d0e56f8dc6292773603d0d6aabbdd62a11ef721d1542d8");
+    }
+
+    #[test]
+    fn test_ntlm() {
+        check(HashType::Ntlm, "8846f7eaee8fb117ad06bdd830b7586c");
+    }
+
+    #[test]
+    fn test_sha3() {
+        check(HashType::Sha3_224, "c3f847612c3780385a859a1993dfd9fe7c4e6d7f477148e527e9374c");
+        check(HashType::Sha3_256, "c0067d4af4e87f00dbac63b6156828237059172d1bbeac67427345d6a9fda484");
+        check(HashType::Sha3_384, "9c1565e99afa2ce7800e96a73c125363c06697c5674d59f227b3368fd00b85ead506eefa90702673d873cb2c9357eafc");
+        check(HashType::Sha3_512, "e9a75486736a550af4fea861e2378305c4a555a05094dee1dca2f68afea49cc3a50e8de6ea131ea521311f4d6fb054a146e8282f8e35ff2e6368c1a62e909716");
+    }
+
+    #[test]
+    fn test_shake() {
+        check(HashType::Shake128, "0ac28ef634f3a8415ae5ef6e614bf11f1a18df4d1fa05a4dd1e6a0acd93bfc57");
+        check(HashType::Shake256, "a5ee08f8e3abe7d592f6de77f1d3298a1149eba68b97f091c90b7736a1be63ab2d425f94c5346cac64807f20f654c5ad9063a4d12902c5e45533491215754883");
+    }
+
+    #[test]
+    fn test_keccak() {
+        check(HashType::Keccak256, "b68fe43f0d1a0d7aef123722670be50268e15365401c442f8806ef83b612976b");
+        check(HashType::Keccak512, "a6818b8188b36c44d17784c5551f63accc5deaf8786f9d0ad1ae3cd8d887cbab4f777286dbb315fb14854c8774dc0d10b5567e4a705536cc2a1d61ec0a16a7a6");
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert!("sha4".parse::<HashType>().is_err());
+    }
+}
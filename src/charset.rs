@@ -0,0 +1,180 @@
+//! Alphabets of the passwords, shared by the reduction function and the password arithmetic.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Predefined charsets: name and characters.
+const PRESETS: [(&str, &str); 7] = [
+    ("digits", "0123456789"),
+    ("lower", "abcdefghijklmnopqrstuvwxyz"),
+    ("upper", "ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+    ("lower-digits", "abcdefghijklmnopqrstuvwxyz0123456789"),
+    ("mixalpha-numeric", "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"),
+    ("base64", "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!*"),
+    ("ascii", " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~"),
+];
+
+/// Name of the default charset.
+pub const DEFAULT_CHARSET: &str = "base64";
+
+/// Alphabet of the tables generated before the charsets, in the order of the first reduction function
+/// (the byte values 0 to 63 of the hash give `A-Z a-z 0-9 ! *`).
+pub const LEGACY_CHARSET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!*";
+
+/// An alphabet of any size.
+/// # Fields
+/// * `chars` - The characters, in the order of their values
+/// * `values` - The value of each character
+/// # Note
+/// The value of a character is its position in the alphabet:
+/// a password is a number written in base `len()` with these digits.
+#[derive(Clone, Debug)]
+pub struct Charset {
+    chars: Vec<char>,
+    values: HashMap<char, u64>,
+}
+
+impl Charset {
+    /// Create a charset from its characters.
+    /// # Arguments
+    /// * `chars` - The characters, in the order of their values
+    /// # Returns
+    /// The charset, or an error if the alphabet is empty, has duplicates or characters that are not printable ASCII.
+    pub fn new(chars: &str) -> Result<Charset, String> {
+        let chars: Vec<char> = chars.chars().collect();
+        if chars.is_empty() {
+            return Err("The charset is empty".to_string());
+        }
+        let mut values = HashMap::new();
+        for (value, c) in chars.iter().enumerate() {
+            if !c.is_ascii() || c.is_ascii_control() {
+                return Err(format!("The charset contains {:?}, which is not a printable ASCII character", c));
+            }
+            if values.insert(*c, value as u64).is_some() {
+                return Err(format!("The charset contains {:?} twice", c));
+            }
+        }
+        Ok(Charset { chars, values })
+    }
+
+    /// Get a predefined charset.
+    /// # Arguments
+    /// * `name` - The name of the preset (digits, lower, upper, lower-digits, mixalpha-numeric, base64, ascii)
+    pub fn preset(name: &str) -> Option<Charset> {
+        PRESETS.iter().find(|(preset, _)| *preset == name).map(|(_, chars)| Charset::new(chars).unwrap())
+    }
+
+    /// Get the name of the preset with the same characters, in the same order.
+    /// # Returns
+    /// The name of the preset, None for a user-defined charset.
+    pub fn preset_name(&self) -> Option<&'static str> {
+        PRESETS.iter().find(|(_, chars)| self.chars.iter().copied().eq(chars.chars())).map(|(name, _)| *name)
+    }
+
+    /// Get the alphabet of the tables generated before the charsets (`A-Z a-z 0-9 ! *`).
+    /// # Note
+    /// It has the characters of the base64 preset in another order: the reduction function of the old tables
+    /// only gives their passwords with this order.
+    pub fn legacy() -> Arc<Charset> {
+        Arc::new(Charset::new(LEGACY_CHARSET).unwrap())
+    }
+
+    /// Get the number of characters.
+    pub fn len(&self) -> u64 {
+        self.chars.len() as u64
+    }
+
+    /// Get the character of a value.
+    /// # Arguments
+    /// * `value` - The value, lower than `len()`
+    pub fn char(&self, value: u64) -> char {
+        self.chars[value as usize]
+    }
+
+    /// Get the value of a character, None if it is not in the charset.
+    pub fn value(&self, c: char) -> Option<u64> {
+        self.values.get(&c).copied()
+    }
+
+    /// Get the number of passwords of a given length.
+    /// # Returns
+    /// `len()^length`, None if it does not fit in a u128.
+    pub fn keyspace(&self, length: usize) -> Option<u128> {
+        (self.len() as u128).checked_pow(length as u32)
+    }
+}
+
+/// Two charsets are equal if they have the same characters in the same order.
+impl PartialEq for Charset {
+    fn eq(&self, other: &Charset) -> bool {
+        self.chars == other.chars
+    }
+}
+
+impl Eq for Charset {}
+
+/// Display the characters of the charset.
+impl Display for Charset {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.chars.iter().collect::<String>())
+    }
+}
+
+/// Parse a preset name, or the characters of a user-defined charset.
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Charset::preset(s) {
+            Some(charset) => Ok(charset),
+            None => Charset::new(s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(Charset::preset("digits").unwrap().len(), 10);
+        assert_eq!(Charset::preset("lower-digits").unwrap().len(), 36);
+        assert_eq!(Charset::preset("mixalpha-numeric").unwrap().len(), 62);
+        assert_eq!(Charset::preset("base64").unwrap().len(), 64);
+        assert_eq!(Charset::preset("ascii").unwrap().len(), 95);
+        assert!(Charset::preset("unknown").is_none());
+        assert_eq!(Charset::preset("lower").unwrap().preset_name(), Some("lower"));
+        assert_eq!(Charset::new("xyz").unwrap().preset_name(), None);
+        assert_eq!(Charset::legacy().preset_name(), None);
+    }
+
+    #[test]
+    fn test_values() {
+        let charset = Charset::preset("base64").unwrap();
+        assert_eq!(charset.value('0'), Some(0));
+        assert_eq!(charset.value('A'), Some(10));
+        assert_eq!(charset.value('*'), Some(63));
+        assert_eq!(charset.value('~'), None);
+        assert_eq!(charset.char(36), 'a');
+        let legacy = Charset::legacy();
+        assert_eq!(legacy.char(0), 'A');
+        assert_eq!(legacy.value('0'), Some(52));
+        assert_eq!(legacy.len(), charset.len());
+    }
+
+    #[test]
+    fn test_user_defined() {
+        let charset = "xyz".parse::<Charset>().unwrap();
+        assert_eq!(charset.len(), 3);
+        assert_eq!(charset.keyspace(4), Some(81));
+        assert_eq!(charset.to_string(), "xyz");
+        assert_eq!("digits".parse::<Charset>().unwrap(), Charset::preset("digits").unwrap());
+        assert!(Charset::new("").is_err());
+        assert!(Charset::new("abca").is_err());
+        assert!(Charset::new("ab\n").is_err());
+        assert!(Charset::new("abé").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! Checkpoint of a generation, to resume it where it stopped.
+//!
+//! The chains of each table file (a partition) are generated from the start points `file, file + step, file + 2 step...`:
+//! the checkpoint records the next start point of each partition, its number of chains and the length of its file,
+//! with the parameters of the table. It does not depend on the number of threads of the generation.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::generation::start_step;
+use crate::manifest::{field, fields, Manifest};
+use crate::password::Password;
+use crate::table::table_path;
+
+/// Name of the checkpoint file in the table folder.
+pub const CHECKPOINT_FILE: &str = "checkpoint.txt";
+
+/// Version of the checkpoint written by this program.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// The state of a table file.
+/// # Fields
+/// * `cursor` - The keyspace index of the next start point, None once the end of the keyspace is reached
+/// * `chains` - The number of chains of the file
+/// * `length` - The length of the file in bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Partition {
+    pub cursor: Option<u128>,
+    pub chains: u64,
+    pub length: u64,
+}
+
+/// The checkpoint of a generation.
+/// # Fields
+/// * `version` - The version of the checkpoint
+/// * `parameters` - The parameters of the table, which must not change when the generation is resumed
+/// * `partitions` - The state of each table file, the files of the first table first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub parameters: Vec<(String, String)>,
+    pub partitions: Vec<Partition>,
+}
+
+impl Checkpoint {
+    /// Create the checkpoint of a table.
+    /// # Arguments
+    /// * `manifest` - The parameters of the table
+    /// * `partitions` - The state of each table file
+    pub fn new(manifest: &Manifest, partitions: Vec<Partition>) -> Checkpoint {
+        Checkpoint { version: CHECKPOINT_VERSION, parameters: parameters(manifest), partitions }
+    }
+
+    /// Read the checkpoint of a table folder.
+    /// # Arguments
+    /// * `path` - The folder containing the rainbow table
+    /// # Returns
+    /// The checkpoint, None if the folder does not contain one.
+    pub fn read(path: &Path) -> Result<Option<Checkpoint>, String> {
+        let file = path.join(CHECKPOINT_FILE);
+        if !file.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&file).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+        Checkpoint::parse(&contents).map(Some).map_err(|e| format!("Invalid {}: {}", file.display(), e))
+    }
+
+    /// Parse the content of a checkpoint file.
+    /// # Arguments
+    /// * `contents` - The `key = value` lines of the checkpoint
+    /// # Returns
+    /// The checkpoint, or an error if a field is missing or invalid.
+    pub fn parse(contents: &str) -> Result<Checkpoint, String> {
+        let fields = fields(contents);
+        let version: u32 = field(&fields, "checkpoint_version")?;
+        if version != CHECKPOINT_VERSION {
+            return Err(format!("Unsupported checkpoint version {} (expected {})", version, CHECKPOINT_VERSION));
+        }
+        let partitions = (0..field::<usize>(&fields, "partitions")?).map(|k| {
+            let key = format!("partition_{}", k);
+            let value: String = field(&fields, &key)?;
+            let invalid = || format!("Invalid value for {}: {}", key, value);
+            let values: Vec<&str> = value.split(',').map(|v| v.trim()).collect();
+            let [cursor, chains, length] = values[..] else {
+                return Err(invalid());
+            };
+            Ok(Partition {
+                cursor: if cursor == "end" { None } else { Some(cursor.parse().map_err(|_| invalid())?) },
+                chains: chains.parse().map_err(|_| invalid())?,
+                length: length.parse().map_err(|_| invalid())?,
+            })
+        }).collect::<Result<Vec<Partition>, String>>()?;
+        // The parameters are the other fields, checked against the manifest
+        let mut parameters: Vec<(String, String)> = fields.iter()
+            .filter(|(key, _)| !key.starts_with("partition") && **key != "checkpoint_version")
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        parameters.sort();
+        Ok(Checkpoint { version, parameters, partitions })
+    }
+
+    /// Write the checkpoint in the table folder.
+    /// # Arguments
+    /// * `path` - The folder containing the rainbow table
+    /// # Note
+    /// The checkpoint is written in a temporary file which then replaces the old one,
+    /// so an interrupted write leaves the previous checkpoint.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let tmp = path.join(format!("{}.tmp", CHECKPOINT_FILE));
+        let mut file = File::create(&tmp)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, path.join(CHECKPOINT_FILE))
+    }
+
+    /// Check that the checkpoint was written for a table.
+    /// # Arguments
+    /// * `manifest` - The parameters of the table
+    /// # Returns
+    /// An error describing the first parameter which differs, or the first cursor which is not a start point of its file.
+    /// # Note
+    /// The start points of the file `k` of a table are `k`, `k + step`, `k + 2 step`... up to the keyspace size.
+    pub fn validate(&self, manifest: &Manifest) -> Result<(), String> {
+        for (key, value) in parameters(manifest) {
+            match self.parameters.iter().find(|(k, _)| *k == key) {
+                Some((_, v)) if *v == value => (),
+                Some((_, v)) => return Err(format!("The checkpoint was written with {} = {}, not {}", key, v, value)),
+                None => return Err(format!("The checkpoint does not record {}", key)),
+            }
+        }
+        if self.partitions.len() as u64 != manifest.tables as u64 * manifest.files {
+            return Err(format!("The checkpoint has {} partitions for {} tables of {} files", self.partitions.len(), manifest.tables, manifest.files));
+        }
+        let step = start_step(manifest.chain_length);
+        for (k, partition) in self.partitions.iter().enumerate() {
+            let Some(cursor) = partition.cursor else {
+                continue;
+            };
+            let first = k as u64 % manifest.files;
+            let offset = Password::from_index(cursor, &manifest.keyspace).and_then(|password| password.checked_sub(first as u128));
+            if offset.is_none_or(|offset| offset.to_index() % step != 0) {
+                return Err(format!("The cursor {} of the partition {} is not one of its start points ({} + a multiple of {}, below {})",
+                    cursor, k, first, step, manifest.keyspace.size()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Bring the table files back to their state at the checkpoint.
+    /// # Arguments
+    /// * `path` - The folder containing the rainbow table
+    /// * `manifest` - The parameters of the table
+    /// # Returns
+    /// The number of records removed, written after the checkpoint, and how many of them were truncated,
+    /// or an error if a file is shorter than at the checkpoint.
+    /// # Note
+    /// The chains written after the checkpoint are generated again from the cursors, they are removed to avoid duplicates.
+    pub fn restore(&self, path: &Path, manifest: &Manifest) -> Result<(u64, u64), String> {
+        let (mut removed, mut truncated) = (0, 0);
+        for (k, partition) in self.partitions.iter().enumerate() {
+            let file = table_path(path, (k as u64 / manifest.files) as u16, k as u64 % manifest.files, manifest.format);
+            let length = fs::metadata(&file).map(|metadata| metadata.len()).unwrap_or(0);
+            if length < partition.length {
+                return Err(format!("{} has {} bytes, {} at the checkpoint", file.display(), length, partition.length));
+            }
+            if length > partition.length {
+                let tail = read_tail(&file, partition.length.max(manifest.format.header_size())).map_err(|e| e.to_string())?;
+                let (records, partial) = count_records(&tail, manifest.format.record_size(&manifest.keyspace));
+                removed += records;
+                truncated += partial as u64;
+                OpenOptions::new().write(true).open(&file).and_then(|f| f.set_len(partition.length)).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok((removed, truncated))
+    }
+}
+
+/// Read the end of a file.
+/// # Arguments
+/// * `file` - The path of the file
+/// * `start` - The position of the first byte read
+fn read_tail(file: &Path, start: u64) -> std::io::Result<Vec<u8>> {
+    let mut tail = Vec::new();
+    let mut file = File::open(file)?;
+    file.seek(SeekFrom::Start(start))?;
+    file.read_to_end(&mut tail)?;
+    Ok(tail)
+}
+
+/// Count the records of a part of a table file starting with a record.
+/// # Arguments
+/// * `bytes` - The bytes of the records
+/// * `record_size` - The size of the records, None for text lines of several lengths
+/// # Returns
+/// The number of records, including a last truncated one, and whether the last one is truncated.
+fn count_records(bytes: &[u8], record_size: Option<u64>) -> (u64, bool) {
+    match record_size {
+        Some(size) => ((bytes.len() as u64).div_ceil(size), !(bytes.len() as u64).is_multiple_of(size)),
+        None => {
+            let partial = bytes.last().is_some_and(|&byte| byte != b'\n');
+            (bytes.iter().filter(|&&byte| byte == b'\n').count() as u64 + partial as u64, partial)
+        },
+    }
+}
+
+/// Write the checkpoint as `key = value` lines.
+impl std::fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "# arc-en-ciel generation checkpoint")?;
+        writeln!(f, "checkpoint_version = {}", self.version)?;
+        for (key, value) in self.parameters.iter() {
+            writeln!(f, "{} = {}", key, value)?;
+        }
+        writeln!(f, "partitions = {}", self.partitions.len())?;
+        for (k, partition) in self.partitions.iter().enumerate() {
+            let cursor = partition.cursor.map(|cursor| cursor.to_string()).unwrap_or("end".to_string());
+            writeln!(f, "partition_{} = {},{},{}", k, cursor, partition.chains, partition.length)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the parameters of a table recorded in its checkpoints, sorted by name.
+fn parameters(manifest: &Manifest) -> Vec<(String, String)> {
+    let mut parameters = vec![
+        ("chain_length".to_string(), manifest.chain_length.to_string()),
+        ("distinguished_bits".to_string(), manifest.distinguished_bits.to_string()),
+        ("files".to_string(), manifest.files.to_string()),
+        ("format".to_string(), manifest.format.to_string()),
+        ("hash".to_string(), manifest.hash.to_string()),
+        ("keyspace".to_string(), manifest.keyspace.to_string()),
+        ("reduction_version".to_string(), manifest.reduction_version.to_string()),
+        ("tables".to_string(), manifest.tables.to_string()),
+    ];
+    parameters.sort();
+    parameters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::algorithm::HashType;
+    use crate::charset::Charset;
+    use crate::keyspace::Keyspace;
+    use crate::table::TableFormat;
+
+    fn manifest(chain_length: u16) -> Manifest {
+        let keyspace = Arc::new(Keyspace::fixed(Arc::new(Charset::preset("digits").unwrap()), 4).unwrap());
+        Manifest::new(TableFormat::Text, HashType::Md5, chain_length, 0, keyspace, 2, 2)
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let partitions = vec![
+            Partition { cursor: Some(140), chains: 10, length: 90 },
+            Partition { cursor: None, chains: 7, length: 63 },
+            Partition { cursor: Some(0), chains: 0, length: 0 },
+            Partition { cursor: Some(995), chains: 71, length: 639 },
+        ];
+        let checkpoint = Checkpoint::new(&manifest(20), partitions);
+        let parsed = Checkpoint::parse(&checkpoint.to_string()).unwrap();
+        assert_eq!(parsed, checkpoint);
+        assert!(parsed.validate(&manifest(20)).is_ok());
+        assert!(Checkpoint::parse(&checkpoint.to_string().replace("partition_1 = end", "partition_1 = ?")).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_validate() {
+        let partitions = (0..4).map(|k| Partition { cursor: Some(k % 2), chains: 0, length: 0 }).collect();
+        let checkpoint = Checkpoint::new(&manifest(20), partitions);
+        assert!(checkpoint.validate(&manifest(20)).is_ok());
+        assert!(checkpoint.validate(&manifest(30)).is_err());
+        let mut other = manifest(20);
+        other.files = 3;
+        other.chains = vec![0; 6];
+        assert!(checkpoint.validate(&other).is_err());
+        assert!(Checkpoint::parse(&checkpoint.to_string().replace("keyspace = ", "keyspace = 5-")).unwrap().validate(&manifest(20)).is_err());
+
+        // Cursors which are not start points of their file (step of 14 for chains of 20)
+        let cursor = |k: usize, cursor: u128| {
+            let mut checkpoint = checkpoint.clone();
+            checkpoint.partitions[k].cursor = Some(cursor);
+            checkpoint.validate(&manifest(20))
+        };
+        assert!(cursor(3, 1 + 14 * 5).is_ok());
+        assert!(cursor(3, 0).is_err());
+        assert!(cursor(3, 14).is_err());
+        assert!(cursor(0, 10_001 * 14).is_err());
+    }
+
+    #[test]
+    fn test_count_records() {
+        assert_eq!(count_records(b"", Some(6)), (0, false));
+        assert_eq!(count_records(&[0; 12], Some(6)), (2, false));
+        assert_eq!(count_records(&[0; 13], Some(6)), (3, true));
+        assert_eq!(count_records(b"a\tbc\nab\tc\n", None), (2, false));
+        assert_eq!(count_records(b"a\tbc\nab\t", None), (2, true));
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-checkpoint");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let manifest = manifest(20);
+        for k in 0..4u64 {
+            fs::write(table_path(&dir, (k / 2) as u16, k % 2, manifest.format), "01234567\n".repeat(k as usize + 1)).unwrap();
+        }
+        let partitions = (0..4).map(|k| Partition { cursor: Some(k), chains: 1, length: 9 }).collect();
+        let checkpoint = Checkpoint::new(&manifest, partitions);
+        assert_eq!(checkpoint.restore(&dir, &manifest), Ok((1 + 2 + 3, 0)));
+        assert_eq!(fs::read_to_string(table_path(&dir, 1, 1, manifest.format)).unwrap(), "01234567\n");
+
+        // A record truncated by an interruption
+        fs::write(table_path(&dir, 1, 0, manifest.format), "01234567\n01234567\n0123").unwrap();
+        assert_eq!(checkpoint.restore(&dir, &manifest), Ok((2, 1)));
+        assert_eq!(fs::read_to_string(table_path(&dir, 1, 0, manifest.format)).unwrap(), "01234567\n");
+
+        // A file shorter than at the checkpoint
+        fs::write(table_path(&dir, 0, 1, manifest.format), "0123").unwrap();
+        assert!(checkpoint.restore(&dir, &manifest).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+//! Estimate what a rainbow table will give before generating it, with the formulas of Philippe Oechslin
+//! ("Making a Faster Cryptanalytic Time-Memory Trade-Off", 2003).
+//!
+//! With `N` passwords and `m` chains, the number of distinct passwords of each column of a table is
+//! `m_1 = m` and `m_{i+1} = N (1 - e^{-m_i / N})`, two chains merging when they reach the same password in the same column.
+//! A table of `t` columns finds a password with the probability `1 - (1 - m_1 / N) ... (1 - m_t / N)`,
+//! and `l` independent tables with the probability `1 - (1 - P)^l`.
+
+use std::fmt::Display;
+
+use crate::keyspace::Keyspace;
+use crate::table::TableFormat;
+
+/// The expected results of rainbow tables.
+/// # Fields
+/// * `keyspace_size` - The number of passwords of the keyspace
+/// * `chains` - The number of chains of each table
+/// * `chain_length` - The length of the chains
+/// * `tables` - The number of tables
+/// * `table_success` - The probability of finding a password with one table
+/// * `success` - The probability of finding a password with all the tables
+/// * `unique_chains` - The expected number of distinct endpoints of a table (the chains of a perfect table)
+/// * `sizes` - The size of the tables in each format, and after removing the merged chains, in bytes
+/// * `precomputation` - The number of hashes computed for each hash and each table before reading the table
+/// * `false_alarms` - The expected number of chains matching a hash and each table, which are computed again
+/// * `false_alarm_cost` - The expected number of hashes computed by these chains
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    pub keyspace_size: u128,
+    pub chains: u64,
+    pub chain_length: u16,
+    pub tables: u16,
+    pub table_success: f64,
+    pub success: f64,
+    pub unique_chains: f64,
+    pub sizes: Vec<(TableFormat, f64, f64)>,
+    pub precomputation: f64,
+    pub false_alarms: f64,
+    pub false_alarm_cost: f64,
+}
+
+impl Estimate {
+    /// Estimate rainbow tables.
+    /// # Arguments
+    /// * `keyspace` - The passwords of the tables
+    /// * `chains` - The number of chains of each table
+    /// * `chain_length` - The length of the chains
+    /// * `tables` - The number of tables
+    /// # Returns
+    /// The estimate, or an error if there are more chains than passwords.
+    /// # Note
+    /// The search of this program first computes the `t` candidate endpoints of the hash (`t (t - 1) / 2` hashes),
+    /// then computes again each chain whose endpoint matches, up to the column of the candidate.
+    /// A candidate of the column `c` matches when its chain merges with a chain of the table in one of the next columns.
+    pub fn new(keyspace: &Keyspace, chains: u64, chain_length: u16, tables: u16) -> Result<Estimate, String> {
+        let n = keyspace.size() as f64;
+        if chains == 0 || chains as u128 > keyspace.size() {
+            return Err(format!("A table needs between 1 and {} chains (the number of passwords), not {}", keyspace.size(), chains));
+        }
+        let t = chain_length as usize;
+
+        // Distinct passwords of the columns 0 to t (the endpoints)
+        let mut columns = vec![chains as f64];
+        for i in 0..t {
+            columns.push(n * -(-columns[i] / n).exp_m1());
+        }
+
+        let table_success = 1.0 - columns[..t].iter().map(|m| 1.0 - m / n).product::<f64>();
+        let success = 1.0 - (1.0 - table_success).powi(tables as i32);
+
+        // Probability that the candidate of the column c reaches an endpoint of the table
+        let mut alarm = vec![0.0; t];
+        let mut miss = 1.0;
+        for c in (0..t).rev() {
+            miss *= 1.0 - columns[c + 1] / n;
+            alarm[c] = 1.0 - miss;
+        }
+        let false_alarms = alarm.iter().sum();
+        let false_alarm_cost = alarm.iter().enumerate().map(|(c, p)| p * (c + 1) as f64).sum();
+
+        let unique_chains = columns[t];
+        let sizes = [TableFormat::Text, TableFormat::Binary].into_iter().map(|format| {
+            let record = format.mean_record_size(keyspace) * tables as f64;
+            (format, record * chains as f64, record * unique_chains)
+        }).collect();
+
+        Ok(Estimate {
+            keyspace_size: keyspace.size(),
+            chains,
+            chain_length,
+            tables,
+            table_success,
+            success,
+            unique_chains,
+            sizes,
+            precomputation: (t * t.saturating_sub(1)) as f64 / 2.0,
+            false_alarms,
+            false_alarm_cost,
+        })
+    }
+
+    /// Get the expected number of hashes computed to search a hash which is not in the tables.
+    pub fn search_cost(&self) -> f64 {
+        self.tables as f64 * (self.precomputation + self.false_alarm_cost)
+    }
+
+    /// Get the number of hashes computed to generate the tables.
+    pub fn generation_cost(&self) -> f64 {
+        self.tables as f64 * self.chains as f64 * self.chain_length as f64
+    }
+}
+
+/// Display the estimate as a report.
+impl Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Keyspace: {} passwords", self.keyspace_size)?;
+        writeln!(f, "Tables: {} of {} chains of length {}", self.tables, self.chains, self.chain_length)?;
+        writeln!(f, "Success probability: {:.2}% ({:.2}% per table)", 100.0 * self.success, 100.0 * self.table_success)?;
+        writeln!(f, "Coverage: {:.0} distinct passwords", self.success * self.keyspace_size as f64)?;
+        writeln!(f, "Distinct endpoints: {:.0} per table ({:.2}% of the chains)", self.unique_chains, 100.0 * self.unique_chains / self.chains as f64)?;
+        for (format, size, perfect) in self.sizes.iter() {
+            writeln!(f, "Size ({}): {} ({} perfect)", format, bytes(*size), bytes(*perfect))?;
+        }
+        writeln!(f, "Generation cost: {:.3e} hashes", self.generation_cost())?;
+        writeln!(f, "Search cost: {:.0} hashes per hash ({:.0} to compute the endpoints, {:.1} false alarms costing {:.0} per table)",
+            self.search_cost(), self.precomputation, self.false_alarms, self.false_alarm_cost)
+    }
+}
+
+/// Estimate rainbow tables (main function).
+/// # Arguments
+/// * `keyspace` - The passwords of the tables
+/// * `chains` - The number of chains of each table
+/// * `chain_length` - The length of the chains
+/// * `tables` - The number of tables
+/// # Returns
+/// An error if the parameters are invalid.
+pub fn estimate_main(keyspace: Keyspace, chains: u64, chain_length: u16, tables: u16) -> Result<(), String> {
+    if chain_length == 0 || tables == 0 {
+        return Err("The chain length and the number of tables must be at least 1".to_string());
+    }
+    print!("{}", Estimate::new(&keyspace, chains, chain_length, tables)?);
+    Ok(())
+}
+
+/// Format a number of bytes with a binary unit.
+pub fn bytes(size: f64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut size = size;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, units[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::charset::Charset;
+
+    /// The passwords of a length with a preset charset.
+    fn keyspace(charset: &str, length: usize) -> Keyspace {
+        Keyspace::fixed(Arc::new(Charset::preset(charset).unwrap()), length).unwrap()
+    }
+
+    #[test]
+    fn test_estimate_single_column() {
+        // Without reduction, a table only covers its start points
+        let keyspace = keyspace("digits", 4);
+        let estimate = Estimate::new(&keyspace, 1000, 1, 1).unwrap();
+        assert!((estimate.table_success - 0.1).abs() < 1e-12);
+        assert_eq!(estimate.precomputation, 0.0);
+        let estimate = Estimate::new(&keyspace, 1000, 1, 3).unwrap();
+        assert!((estimate.success - (1.0 - 0.9f64.powi(3))).abs() < 1e-12);
+        assert!(Estimate::new(&keyspace, 10001, 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_estimate_merges() {
+        let keyspace = keyspace("base64", 7);
+        // Few chains: almost no merge, the table covers m t passwords
+        let estimate = Estimate::new(&keyspace, 1000, 100, 1).unwrap();
+        let n = keyspace.size() as f64;
+        assert!((estimate.table_success * n / 100_000.0 - 1.0).abs() < 1e-3);
+        assert!((estimate.unique_chains - 1000.0).abs() < 1e-3);
+        assert_eq!(estimate.precomputation, 4950.0);
+        // Many chains: the merges limit the success probability (without merges, 1 - 0.99^1000 > 0.9999)
+        let estimate = Estimate::new(&keyspace, (keyspace.size() / 100) as u64, 1000, 1).unwrap();
+        assert!(estimate.table_success < 0.98);
+        assert!(estimate.unique_chains < estimate.chains as f64 / 2.0);
+        assert!(estimate.false_alarms > 1.0);
+    }
+
+    #[test]
+    fn test_estimate_sizes() {
+        let keyspace = keyspace("base64", 7);
+        let estimate = Estimate::new(&keyspace, 1000, 100, 2).unwrap();
+        assert_eq!(estimate.sizes[0], (TableFormat::Text, 30_000.0, 2.0 * 15.0 * estimate.unique_chains));
+        assert_eq!(estimate.sizes[1].1, 24_000.0);
+        assert_eq!(bytes(1536.0), "1.5 KiB");
+        assert_eq!(bytes(12.0), "12.0 B");
+    }
+}
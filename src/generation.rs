@@ -1,132 +1,319 @@
-//! Generate the passwords while the stop_me flag is not set and the password is not "?"
+//! Generate the passwords while the stop_me flag is not set and the end of the keyspace is not reached
 
 use rayon::prelude::*;
-use std::fs::OpenOptions;
-use std::io::{Write, Read};
+use std::io;
 use std::mem;
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-use std::sync::Mutex;
-use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use crate::keyspace::Keyspace;
 use crate::password::Password;
-use crate::reduction::reduction;
-use crate::sha3::hash_password;
+use crate::algorithm::{HashAlgorithm, HashType};
+use crate::table::{open_writer, table_path, Chain, TableFormat};
+use crate::manifest::{check_override, Manifest};
+use crate::checkpoint::{Checkpoint, Partition, CHECKPOINT_FILE};
+use crate::sort::{sort_main, RUN_CHAINS};
+use crate::progress::{GenerationCounters, Progress};
 
-const CHAIN_LENGTH_MIN : u16 = 1;
-const CHAIN_LENGTH_MAX : u16 = 2048;
+pub const CHAIN_LENGTH_MIN : u16 = 1;
+pub const CHAIN_LENGTH_MAX : u16 = 2048;
+
+/// Number of start points of a table file used by a round, the checkpoint being written after each round.
+const ROUND_CHAINS: u64 = 4096;
+
+/// Conditions ending the generation before all the start points are used.
+/// # Fields
+/// * `chains` - The number of chains of each table
+/// * `coverage` - The fraction of the start points used, between 0 and 1
+/// * `time` - The duration of the run
+/// * `max_bytes` - The size of the table files, in bytes
+/// # Note
+/// The generation stops at the first condition reached, then it can be resumed with other conditions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StopConditions {
+    pub chains: Option<u64>,
+    pub coverage: Option<f64>,
+    pub time: Option<Duration>,
+    pub max_bytes: Option<u64>,
+}
 
 /// Generate the rainbow table (main function).
 /// # Arguments
 /// * `path` - The path to the file containing the passwords
-/// * `use_mem` - Use memory file
-/// * `chain_length` - The length of the chain
-/// * `password_length` - The length of the password
+/// * `use_mem` - Resume the generation from the checkpoint
+/// * `chain_length` - The length of the chain, the maximum length of the chains with distinguished points
+/// * `distinguished_bits` - End the chains at the first password whose keyspace index has this number of low bits at zero, 0 for chains of `chain_length` passwords
+/// * `format` - The format of the table files
+/// * `perfect` - Build a perfect table (one chain per endpoint) when the generation stops, it cannot be extended afterwards
+/// * `hash_type` - The hash algorithm of the table
+/// * `keyspace` - The passwords of the table (charset and lengths, or mask)
+/// * `tables` - The number of tables, each with its own reduction function
+/// * `threads` - The number of threads, the number of CPUs if not given
+/// * `quiet` - Do not print the progress of the generation
+/// * `stop` - The conditions ending the generation before all the start points are used
+/// # Returns
+/// An error if the parameters are invalid or do not match the existing table.
 /// # Note
-/// If the memory file exists, use it to generate the rainbow table from the last password in the memory file.
-/// If the memory file does not exist, generate the rainbow table and store the last password if the program is stopped.
+/// If the checkpoint exists, check that it was written for the same table and resume each table file from its next start point.
+/// The records written after the checkpoint, which may end with a truncated record, are removed before resuming.
+/// After each round of chains and when the generation stops (Ctrl-C or stop condition), the next start points are written in the checkpoint (`checkpoint.txt`).
+/// A new table has a file per thread, the table files then keep the partitioning recorded in the manifest whatever the number of threads.
 /// Chain length must be between 1 and 2048.
-pub fn generation_main(path: Option<std::path::PathBuf>, use_mem: bool, chain_length: u16, password_length: usize) {
-    let path = path.unwrap().to_str().unwrap().to_string();
-    let thread = num_cpus::get() as u64;
+/// The tables are generated in the same folder from the same start points, the threads of every table running together.
+/// The chains which do not reach a distinguished point within `chain_length` passwords are dropped.
+/// The parameters of the tables are written in the manifest of the folder.
+/// The progress (chains per second, start points used, bytes written and time left) is printed every second.
+#[allow(clippy::too_many_arguments)]
+pub fn generation_main(path: Option<PathBuf>, use_mem: bool, chain_length: u16, distinguished_bits: u8, keyspace: Keyspace, tables: u16, format: TableFormat, perfect: bool, hash_type: HashType, threads: Option<usize>, quiet: bool, stop: StopConditions) -> Result<(), String> {
+    let path = path.unwrap();
+    let threads = threads.unwrap_or_else(num_cpus::get);
+    if threads == 0 {
+        return Err("At least one thread is needed".to_string());
+    }
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| e.to_string())?;
 
-    if chain_length < CHAIN_LENGTH_MIN || chain_length > CHAIN_LENGTH_MAX {
-        panic!("Chain length must be between {} and {}", CHAIN_LENGTH_MIN, CHAIN_LENGTH_MAX);
+    if !(CHAIN_LENGTH_MIN..=CHAIN_LENGTH_MAX).contains(&chain_length) {
+        return Err(format!("Chain length must be between {} and {}", CHAIN_LENGTH_MIN, CHAIN_LENGTH_MAX));
+    }
+    if tables == 0 {
+        return Err("At least one table must be generated".to_string());
+    }
+    if stop.coverage.is_some_and(|coverage| !(coverage > 0.0 && coverage <= 1.0)) {
+        return Err(format!("The coverage must be between 0 and 1, not {}", stop.coverage.unwrap()));
     }
 
+    // The chains are appended to the existing table, which must have the same parameters
+    let mut manifest = match Manifest::read(&path)? {
+        Some(manifest) => {
+            manifest.validate()?;
+            if manifest.perfect {
+                return Err(format!("The table in {} is perfect: its table files were replaced by its sorted table, it cannot be extended", path.display()));
+            }
+            check_override("format", Some(format), manifest.format)?;
+            check_override("hash_type", Some(hash_type), manifest.hash)?;
+            check_override("chain_length", Some(chain_length), manifest.chain_length)?;
+            check_override("distinguished_bits", Some(distinguished_bits), manifest.distinguished_bits)?;
+            check_override("keyspace", Some(&keyspace), manifest.keyspace.as_ref())?;
+            check_override("tables", Some(tables), manifest.tables)?;
+            manifest
+        },
+        None => {
+            let manifest = Manifest::new(format, hash_type, chain_length, distinguished_bits, Arc::new(keyspace), tables, threads as u64);
+            manifest.validate()?;
+            manifest
+        },
+    };
+
     // Propertly stop the program
-    let stop_me: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
-    let stop_me_ctrlc = stop_me.clone();
-    ctrlc::set_handler(move || {
-        println!("Ctrl-C received! Exiting...");
-        stop_me_ctrlc.store(true, std::sync::atomic::Ordering::SeqCst);
-    }).expect("Error setting Ctrl-C handler");
-
-    let mut passwords: Vec<Password> = Vec::new();
-    // check if memory file exists
-    if use_mem && std::path::Path::new(&(path.clone() + "mem.txt")).exists() {
-        // Read the memory file and store the passwords in a vector
-        let mut file = OpenOptions::new()
-            .read(true)
-            .open(path.clone() + "mem.txt")
-            .unwrap();
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).unwrap();
-        let mut passwords_str = contents.split("\n").collect::<Vec<&str>>();
-        passwords_str.pop();
-        passwords_str.par_sort();
-        passwords = passwords_str.into_par_iter().map(|p| Password::new(p.to_string())).collect();
-    } else {
-        // Generate the first password for each thread
-        for i in 0..thread {
-            passwords.push(Password::new("0".repeat(password_length).to_string()) + i);
-        }
+    let stop_me = stop_flag();
+
+    // State of each table file: the cursors of the checkpoint, or the first start points
+    let files = tables as u64 * manifest.files;
+    let checkpoint = match Checkpoint::read(&path)?.filter(|_| use_mem) {
+        Some(checkpoint) => {
+            checkpoint.validate(&manifest)?;
+            checkpoint
+        },
+        None if manifest.chains.iter().any(|&chains| chains > 0) => {
+            return Err(format!("The table in {} has chains and is not resumed from its {}: resume it, or remove the folder to generate it again", path.display(), CHECKPOINT_FILE));
+        },
+        None => Checkpoint::new(&manifest, (0..files).map(|i| Partition { cursor: Some((i % manifest.files) as u128), chains: 0, length: 0 }).collect()),
+    };
+    // Remove the records written after the checkpoint, such as a record truncated by an interruption
+    let (removed, truncated) = checkpoint.restore(&path, &manifest)?;
+    if removed > 0 {
+        println!("{} records written after the checkpoint discarded ({} truncated)", removed, truncated);
     }
+    let mut partitions = checkpoint.partitions;
+
+    std::fs::create_dir_all(&path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+    // The sorted table will not contain the new chains
+    manifest.sorted = false;
+    manifest.write(&path).map_err(|e| format!("Cannot write the manifest: {}", e))?;
 
-    // Create the memory file
-    std::fs::create_dir_all(PathBuf::from(path.clone()).to_str().unwrap()).unwrap();
-
-    let mem_file = Mutex::new(
-        OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open(path.clone() + "mem.txt")
-        .unwrap()
-    );
-    
-    // Generate the rainbow table
-    (0..thread).into_par_iter().for_each(|i: u64| {
-        let password = generation(&stop_me, i, passwords[i as usize].clone(), chain_length, path.clone(), password_length);
-        mem_file.lock().unwrap().write_all(format!("{}\n", password.password).as_bytes()).unwrap();
+    // Start points of each table file, the table files of a table sharing the start points
+    let step = start_step(manifest.chain_length);
+    let size = manifest.keyspace.size();
+    let per_table = manifest.files;
+    let first = |i: u64| i % per_table;
+    let file_starts = |i: u64| size.saturating_sub(first(i) as u128).div_ceil(step);
+    let used_starts = |i: u64, partition: &Partition| partition.cursor.map(|cursor| cursor.saturating_sub(first(i) as u128) / step).unwrap_or(file_starts(i));
+
+    // Start points and chains of each table file allowed by the stop conditions
+    let max_starts = |i: u64| stop.coverage.map(|coverage| (coverage * file_starts(i) as f64).ceil() as u128).unwrap_or(u128::MAX);
+    let max_chains = |i: u64| stop.chains.map(|chains| chains / per_table + (first(i) < chains % per_table) as u64).unwrap_or(u64::MAX);
+    // The largest record, so the table files never exceed the disk budget
+    let record_size = manifest.format.record_size(&manifest.keyspace).unwrap_or(2 * manifest.keyspace.max_length as u64 + 2);
+    let header_size = manifest.format.header_size();
+    let round_limits = |partitions: &[Partition]| -> Vec<(u64, u64)> {
+        let mut limits: Vec<(u64, u64)> = partitions.iter().enumerate().map(|(i, partition)| match partition.cursor {
+            Some(_) => (max_starts(i as u64).saturating_sub(used_starts(i as u64, partition)).min(ROUND_CHAINS as u128) as u64, max_chains(i as u64).saturating_sub(partition.chains)),
+            None => (0, 0),
+        }).collect();
+        if let Some(max_bytes) = stop.max_bytes {
+            let active: Vec<usize> = (0..limits.len()).filter(|&i| limits[i].0 > 0 && limits[i].1 > 0).collect();
+            let headers = active.iter().filter(|&&i| partitions[i].length == 0).count() as u64 * header_size;
+            let written: u64 = partitions.iter().map(|partition| partition.length).sum();
+            // The records left are shared between the active table files
+            let allowed = max_bytes.saturating_sub(written + headers) / record_size;
+            let count = active.len().max(1) as u64;
+            for (k, i) in active.into_iter().enumerate() {
+                limits[i].1 = limits[i].1.min(allowed / count + ((k as u64) < allowed % count) as u64);
+            }
+        }
+        limits
+    };
+    let deadline = stop.time.map(|time| Instant::now() + time);
+
+    // Progress of the generation, from the start points used by each table file
+    let total_starts: u128 = (0..files).map(file_starts).sum();
+    let end_starts: u128 = (0..files).map(|i| file_starts(i).min(max_starts(i))).sum();
+    let starts: u128 = partitions.iter().enumerate().map(|(i, partition)| used_starts(i as u64, partition)).sum();
+    let chains: u64 = partitions.iter().map(|partition| partition.chains).sum();
+    let target = stop.chains.map(|chains| chains * tables as u64);
+    let table_files: Vec<PathBuf> = (0..files).map(|i| table_path(&path, (i / per_table) as u16, first(i), manifest.format)).collect();
+    let counters = Arc::new(GenerationCounters::default());
+    let counters_progress = counters.clone();
+    let progress = Progress::start(quiet, move |elapsed| {
+        let written = table_files.iter().filter_map(|file| std::fs::metadata(file).ok()).map(|metadata| metadata.len()).sum();
+        counters_progress.line(elapsed, chains, starts, total_starts, end_starts, target, written)
     });
 
-    // Close the memory file
-    mem_file.lock().unwrap().sync_all().unwrap();
-    mem_file.lock().unwrap().flush().unwrap();
-    mem::drop(mem_file);
+    // Generate the rainbow tables by rounds, each table file being a partition of the start points
+    let mut limited = false;
+    while !stop_me.load(Ordering::Relaxed) {
+        let limits = round_limits(&partitions);
+        limited = deadline.is_some_and(|deadline| Instant::now() >= deadline) || limits.iter().all(|&(starts, chains)| starts == 0 || chains == 0);
+        if limited {
+            break;
+        }
+        let rounds = pool.install(|| (0..files).into_par_iter().map(|i: u64| {
+            let start = partitions[i as usize].cursor.and_then(|cursor| Password::from_index(cursor, &manifest.keyspace));
+            let (max_starts, max_chains) = limits[i as usize];
+            if max_starts == 0 || max_chains == 0 {
+                return Ok((start, 0));
+            }
+            generation(&hash_type, &stop_me, deadline, (i / per_table) as u16, first(i), start, max_starts, max_chains, &path, &manifest, &counters)
+                .map_err(|e| format!("Cannot write {}: {}", table_path(&path, (i / per_table) as u16, first(i), manifest.format).display(), e))
+        }).collect::<Result<Vec<(Option<Password>, u64)>, String>>())?;
+
+        // Record the number of chains generated and the next start points
+        for (i, (cursor, chains)) in rounds.into_iter().enumerate() {
+            partitions[i] = Partition {
+                cursor: cursor.map(|password| password.to_index()),
+                chains: partitions[i].chains + chains,
+                length: std::fs::metadata(table_path(&path, (i as u64 / per_table) as u16, first(i as u64), manifest.format)).map(|metadata| metadata.len()).unwrap_or(0),
+            };
+            manifest.chains[i] = partitions[i].chains;
+        }
+        Checkpoint::new(&manifest, partitions.clone()).write(&path).map_err(|e| format!("Cannot write the checkpoint: {}", e))?;
+        manifest.write(&path).map_err(|e| format!("Cannot write the manifest: {}", e))?;
+    }
+    progress.finish();
+    if limited && partitions.iter().any(|partition| partition.cursor.is_some()) {
+        println!("Stop condition reached, the generation can be resumed from the checkpoint");
+    }
+
+    // Remove the merged chains
+    if perfect {
+        sort_main(path, RUN_CHAINS, true)?;
+    }
+    Ok(())
 }
 
-/// Generate the rainbow table (sub function).
+/// Get the flag set by Ctrl-C to stop the generation.
+/// # Note
+/// The handler is set once per process, the generations of the process share the flag.
+fn stop_flag() -> Arc<AtomicBool> {
+    static STOP_ME: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    STOP_ME.get_or_init(|| {
+        let stop_me = Arc::new(AtomicBool::new(false));
+        let stop_me_ctrlc = stop_me.clone();
+        ctrlc::set_handler(move || {
+            println!("Ctrl-C received! Exiting...");
+            stop_me_ctrlc.store(true, std::sync::atomic::Ordering::SeqCst);
+        }).expect("Error setting Ctrl-C handler");
+        stop_me
+    }).clone()
+}
+
+/// Get the distance between two start points of a table file.
+/// # Arguments
+/// * `chain_length` - The length of the chains
+pub fn start_step(chain_length: u16) -> u128 {
+    ((chain_length as f32 * 0.7) as u128).max(1)
+}
+
+/// Generate a round of chains of a table file (sub function).
 /// # Arguments
+/// * `hasher` - The hash algorithm
 /// * `stop_me` - The flag to stop the program
+/// * `deadline` - The end of the generation, if it is limited in time
+/// * `table` - The index of the table
 /// * `i` - The thread number
-/// * `start` - The first password
-/// * `chain_length` - The length of the chain
+/// * `start` - The first password, None if the thread has used all its start points
+/// * `max_starts` - The maximum number of start points used by the round
+/// * `max_chains` - The maximum number of chains written by the round
 /// * `path` - The path to the file containing the passwords
-/// * `password_length` - The length of the password
+/// * `manifest` - The parameters of the table (chain length, keyspace and format)
+/// * `counters` - The progress of the generation, updated after each chain
 /// # Returns
-/// The last password.
-fn generation(stop_me: &Arc<AtomicBool>, i: u64, start: Password, chain_length: u16, path: String, password_length: usize ) -> Password {
+/// The next start point, None once the end of the keyspace is reached, and the number of chains written,
+/// or an error if the table file cannot be written.
+/// # Note
+/// A round stops after at most `ROUND_CHAINS` start points, so every table file progresses even with fewer threads than files.
+#[allow(clippy::too_many_arguments)]
+fn generation<H: HashAlgorithm>(hasher: &H, stop_me: &Arc<AtomicBool>, deadline: Option<Instant>, table: u16, i: u64, start: Option<Password>, max_starts: u64, max_chains: u64, path: &Path, manifest: &Manifest, counters: &GenerationCounters) -> io::Result<(Option<Password>, u64)> {
+    let chain_length = manifest.chain_length;
+    let reduction = manifest.reduction(table).map_err(io::Error::other)?;
+
     // Open a file in in append mode
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(path + format!("test_{}.txt", i).as_str())
-        .unwrap();
+    let mut file = open_writer(&table_path(path, table, i, manifest.format), manifest.format, manifest.hash, &manifest.keyspace)?;
 
     // Create the first password
     let mut password = start;
-    let offset = (chain_length as f32 * 0.7) as u64 ;
-    // Generate the passwords while the stop_me flag is not set and the password is not "?"
-    while !stop_me.load(std::sync::atomic::Ordering::Relaxed) && password.password != "?" {
-        let mut password_tmp = password.password.clone();
+    let offset = start_step(chain_length);
+    let mut chains = 0;
+    // Buffers of the passwords and of the digests, reused by every chain
+    let mut buffer = Vec::with_capacity(manifest.keyspace.max_length);
+    let mut digest = vec![0; hasher.digest_size()];
+    // Generate the passwords while the stop_me flag is not set and the end of the keyspace, of the time or of the round is not reached
+    let running = || !stop_me.load(Ordering::Relaxed) && deadline.is_none_or(|deadline| Instant::now() < deadline);
+    for _ in 0..max_starts {
+        let Some(start) = password.as_ref().filter(|_| chains < max_chains && running()) else {
+            break;
+        };
+        let mut index = start.to_index();
 
         // Generate the chain
-        for offset in 0..chain_length {
-            let hash = hash_password(&password_tmp);
-            password_tmp = reduction(&hash, offset, password_length);
+        let end = if manifest.distinguished_bits == 0 {
+            for offset in 0..chain_length {
+                manifest.keyspace.password_into(index, &mut buffer);
+                hasher.hash_into(&buffer, &mut digest);
+                index = reduction.reduce_index(&digest, offset);
+            }
+            Some(index)
+        } else {
+            manifest.keyspace.password_into(index, &mut buffer);
+            hasher.hash_into(&buffer, &mut digest);
+            reduction.distinguished_point(hasher, &digest, manifest.distinguished_bits, chain_length)
+        };
+        // Write the first and last password (or the distinguished point) to the file
+        if let Some(end) = end {
+            file.write_chain(&Chain { start: start.to_index(), end })?;
+            chains += 1;
+            counters.chains.fetch_add(1, Ordering::Relaxed);
         }
-
-        // Write the first and last password to the file
-        file.write_all(format!("{}{}\n", password.password, password_tmp).as_bytes()).unwrap();
-        password = password + offset;
+        counters.starts.fetch_add(1, Ordering::Relaxed);
+        password = start.checked_add(offset);
     }
 
     // Close the file
-    file.sync_all().unwrap();
-    file.flush().unwrap();
+    file.sync()?;
     mem::drop(file);
 
     // Return the last password
-    password
+    Ok((password, chains))
 }
\ No newline at end of file
@@ -18,52 +18,47 @@ impl Hash {
     pub fn new(hash: Vec<u8>) -> Hash {
         Hash { hash }
     }
-
-    /// Convert the hash to a string.
-    pub fn to_string(&self) -> String {
-        let mut hash_str = String::new();
-        for byte in &self.hash {
-            hash_str.push_str(&format!("{:02x}", byte));
-        }
-        hash_str
-    }
 }
 
-/// Implement the From trait for the hash type.
-impl From<String> for Hash {
-    fn from(hash_str: String) -> Self {
-        let mut hash_str = hash_str;
-        let mut hash = Vec::new();
-        for _ in 0..32 {
-            let byte = u8::from_str_radix(&hash_str[0..2], 16).unwrap();
-            hash.push(byte);
-            hash_str = hash_str[2..].to_string();
+/// Implement the TryFrom trait for the hash type.
+/// # Note
+/// The hash is read from its hexadecimal representation of any even length (16 bytes for MD5, 32 bytes for SHA3-256, ...),
+/// an error is returned if the string is not made of pairs of hexadecimal digits.
+impl TryFrom<&str> for Hash {
+    type Error = String;
+
+    fn try_from(hash_str: &str) -> Result<Self, Self::Error> {
+        let hash_str = hash_str.trim();
+        if hash_str.is_empty() || !hash_str.len().is_multiple_of(2) || !hash_str.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid hash: {}", hash_str));
         }
-        Hash::new(hash)
+        let hash = (0..hash_str.len() / 2)
+            .map(|i| u8::from_str_radix(&hash_str[2 * i..2 * i + 2], 16).map_err(|_| format!("Invalid hash: {}", hash_str)))
+            .collect::<Result<Vec<u8>, String>>()?;
+        Ok(Hash::new(hash))
     }
 }
 
-/// Implement the Into trait for the hash type.
-impl Into<String> for Hash {
-    fn into(self) -> String {
-        let mut hash_str = String::new();
-        for byte in self.hash {
-            hash_str.push_str(&format!("{:02x}", byte));
-        }
-        hash_str
+/// Implement the From trait for the string type.
+impl From<Hash> for String {
+    fn from(hash: Hash) -> Self {
+        hash.to_string()
     }
 }
 
 /// Implement the Display trait for the hash type.
 impl fmt::Display for Hash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        for byte in &self.hash {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
     }
 }
 
 /// Implement the Debug trait for the hash type.
 impl fmt::Debug for Hash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
\ No newline at end of file
@@ -1,154 +1,182 @@
 //! Hashing module
 
-use std::collections::HashMap;
-use crate::{hash::Hash, keccak::Keccak};
+use crate::keccak::{Keccak, Padding};
 
-/// SHA-3 struct.  
+/// SHA-3 struct.
 /// # Arguments
-/// * `password` - The password to hash
-/// * `password_bytes` - The password in bytes
 /// * `b` - The block size (b = r + c)
 /// * `c` - Extra block size for more security/operations (SHA-3 norm: c = 2*fingerprint)
 /// * `r` - Rate of bits absorbed by the sponge (r = b - c)
 /// * `fingerprint` - Size of the fingerprint (224, 256, 384, 512)
-#[derive(Debug)]
+/// * `padding` - Padding rule (SHA-3, or the original Keccak of the legacy hashes)
+#[derive(Clone, Copy, Debug)]
 pub struct Sha3 {
-    pub password: String,
-    pub password_bytes: String,
     pub b: i32,
     pub c: i32,
     pub r: i32,
     pub fingerprint: i32,
+    pub padding: Padding,
 }
 
-/// Implementation of the SHA-3 struct.  
+/// Implementation of the SHA-3 struct.
 impl Sha3 {
     /// Create a new SHA-3 instance.
     /// # Arguments
-    /// * `password` - The password to hash
     /// * `fingerprint` - The size of the fingerprint (224, 256, 384, 512)
     /// # Returns
-    /// A new SHA-3 instance
+    /// A new SHA-3 instance, of 256 bits if the fingerprint size is not one of the four sizes.
     /// # Example
     /// ```
-    /// let sha_3 = Sha3::new("password", 256);
+    /// let sha_3 = Sha3::new(256);
     /// ```
-    pub fn new(password: &str, fingerprint: i32) -> Sha3 {
-
-        // Fingerprint values for each block size
-        let mut fingerprint_values = HashMap::new();
-        fingerprint_values.insert(224, 1152);
-        fingerprint_values.insert(256, 1088);
-        fingerprint_values.insert(384, 832);
-        fingerprint_values.insert(512, 576);
-
-        match fingerprint_values.get(&fingerprint) {
-            // If a correct fingerprint is given, we use it
-            Some(b) => Sha3 {
-                password: password.to_string(),
-                password_bytes: String::new(),
-                b: b + 2*fingerprint,
-                c: 2*fingerprint,
-                r: *b,
-                fingerprint,
-            },
+    pub fn new(fingerprint: i32) -> Sha3 {
+        let fingerprint = match fingerprint {
+            224 | 256 | 384 | 512 => fingerprint,
             // If not, we use the default fingerprint size (256 bits)
-            None => {
-                let b = fingerprint_values.get(&256).unwrap();
-                Sha3 {
-                    password: password.to_string(),
-                    password_bytes: String::new(),
-                    b: b + 2*fingerprint,
-                    c: 2*fingerprint,
-                    r: *b,
-                    fingerprint,
-                }
-            }
+            _ => 256,
+        };
+        Sha3 {
+            b: 1600,
+            c: 2*fingerprint,
+            r: 1600 - 2*fingerprint,
+            fingerprint,
+            padding: Padding::Sha3,
         }
     }
 
-    /// Pre-processing function for the SHA-3 algorithm.  
-    /// It converts the password to bytes and adds padding.   
-    /// Passwords are less than r bits long so there is a padding of 0s until the length of the password is a multiple of r.
+    /// Create a new instance of the original Keccak, before the SHA-3 standard (as used by Ethereum).
+    /// It has the same parameters as SHA-3 but no domain bits in its padding, so its digests differ.
     /// # Arguments
-    /// * `self` - The SHA-3 instance
+    /// * `fingerprint` - The size of the fingerprint (224, 256, 384, 512)
+    /// # Returns
+    /// A new Keccak instance, of 256 bits if the fingerprint size is not one of the four sizes.
     /// # Example
     /// ```
-    /// let mut sha_3 = Sha3::new("password", 256);
-    /// sha_3.preprocessing();
+    /// let keccak_256 = Sha3::keccak(256);
     /// ```
-    pub fn preprocessing(&mut self) {
-        // Conversion to bytes
-        self.password_bytes = self.string_to_lsb(self.password.as_str());
-        // Adding the delimiter
-        self.password_bytes.push_str("0000011");
-        // Padding
-        let mut padding = String::new();
-        let padding_len = self.r - (self.password_bytes.len() % self.r as usize) as i32 - 8;
-        for _ in 0..padding_len {
-            padding.push('0');
+    pub fn keccak(fingerprint: i32) -> Sha3 {
+        Sha3 {
+            padding: Padding::Keccak,
+            ..Sha3::new(fingerprint)
         }
-        // Adding the last delimiter
-        padding.push_str("10000000");
-        self.password_bytes.push_str(&padding);
     }
 
-    /// Convert a string to a string of bits (LSB).
+    /// Get the size of the digest in bytes.
+    pub fn digest_size(&self) -> usize {
+        self.fingerprint as usize / 8
+    }
+
+    /// Hashing function using the SHA-3 algorithm, without allocation.
+    /// The message is absorbed by the sponge of the Keccak module with the padding of the instance, then the digest is squeezed.
+    /// The message can have any length: it is absorbed block by block, the padding being added to the last one.
     /// # Arguments
     /// * `self` - The SHA-3 instance
-    /// * `s` - The string to convert
-    /// # Returns
-    /// A string of bits.
-    pub fn string_to_lsb(&self, s: &str) -> String {
-        let mut result = String::new(); // Resulting string (initially empty)
-        // For each character in the string, we convert it to binary and add it to the result
-        for c in s.chars() {
-            result.push_str(&format!("{:08b}", c as u8));
-        }
-        result
+    /// * `message` - The message to hash
+    /// * `digest` - The buffer receiving the digest, of `digest_size()` bytes
+    pub fn hash_into(&self, message: &[u8], digest: &mut [u8]) {
+        // The permutation works on a 1600-bit state (b = r + c)
+        debug_assert_eq!(self.b, 1600);
+        debug_assert_eq!(self.r + self.c, self.b);
+        debug_assert_eq!(digest.len(), self.digest_size());
+        let mut keccak = Keccak::new(self.r as usize / 8);
+        keccak.absorb(message, self.padding);
+        keccak.squeeze(digest);
     }
 
-    /// Hashing function using the SHA-3 algorithm.  
-    /// It calls the preprocessing function and then the sponge function from the Keccak module.  
-    /// The state is then converted to a string of bits.
+    /// Hashing function using the SHA-3 algorithm.
     /// # Arguments
     /// * `self` - The SHA-3 instance
+    /// * `message` - The message to hash
     /// # Returns
-    /// The hashed password.
-    pub fn sha_3(&mut self) -> String {
-        // Message pre-processing (conversion to binary -> adding padding if necessary -> return password in binary)
-        self.preprocessing();
-
-        // Sponge call (from the Keccak module)
-        let mut keccak: Keccak = Keccak::new(self);
-        keccak.sponge();
-        keccak.state_to_strings()
+    /// The digest of the message.
+    /// # Note
+    /// The tables hash with `hash_into`, this allocating version is kept for the tests.
+    #[cfg(test)]
+    pub fn sha_3(&self, message: &[u8]) -> Vec<u8> {
+        let mut digest = vec![0; self.digest_size()];
+        self.hash_into(message, &mut digest);
+        digest
     }
 }
 
-/// Hash a password using the SHA-3 algorithm.
+/// SHAKE struct, the extendable-output functions of SHA-3.
 /// # Arguments
-/// * `password` - The password to hash
-/// * `fingerprint` - The size of the fingerprint (224, 256, 384, 512)
-/// # Returns
-/// The hashed password.
-pub fn sha3_hash(password: &str, fingerprint: Option<i32>) -> Hash {
-    let mut sha_3 = match fingerprint {
-        Some(f) => Sha3::new(password, f),
-        None => Sha3::new(password, 256),
-    };
-    Hash::from(sha_3.sha_3())
+/// * `b` - The block size (b = r + c)
+/// * `c` - Extra block size for more security/operations (c = 2*security)
+/// * `r` - Rate of bits absorbed and squeezed by the sponge (r = b - c)
+/// * `security` - Security strength in bits (128 for SHAKE128, 256 for SHAKE256)
+#[derive(Clone, Copy, Debug)]
+pub struct Shake {
+    pub b: i32,
+    pub c: i32,
+    pub r: i32,
+    pub security: i32,
+}
 
+/// Implementation of the SHAKE struct.
+impl Shake {
+    /// Create a new SHAKE instance.
+    /// # Arguments
+    /// * `security` - The security strength (128, 256)
+    /// # Returns
+    /// A new SHAKE instance, SHAKE256 if the security strength is not 128 or 256.
+    /// # Example
+    /// ```
+    /// let shake = Shake::new(128);
+    /// ```
+    pub fn new(security: i32) -> Shake {
+        let security = match security {
+            128 | 256 => security,
+            _ => 256,
+        };
+        Shake {
+            b: 1600,
+            c: 2*security,
+            r: 1600 - 2*security,
+            security,
+        }
+    }
+
+    /// Hashing function using the SHAKE algorithm, without allocation.
+    /// The message is absorbed with the SHAKE padding, then the output is squeezed, with a permutation every r bits.
+    /// # Arguments
+    /// * `self` - The SHAKE instance
+    /// * `message` - The message to hash
+    /// * `output` - The buffer filled with the output, of any length
+    pub fn hash_into(&self, message: &[u8], output: &mut [u8]) {
+        debug_assert_eq!(self.c, 2*self.security);
+        debug_assert_eq!(self.r + self.c, self.b);
+        let mut keccak = Keccak::new(self.r as usize / 8);
+        keccak.absorb(message, Padding::Shake);
+        keccak.squeeze(output);
+    }
+
+    /// Hashing function using the SHAKE algorithm.
+    /// # Arguments
+    /// * `self` - The SHAKE instance
+    /// * `message` - The message to hash
+    /// * `length` - The length of the output in bytes
+    /// # Returns
+    /// The first `length` bytes of the output of the message.
+    /// # Note
+    /// The tables hash with `hash_into`, this allocating version is kept for the tests.
+    #[cfg(test)]
+    pub fn shake(&self, message: &[u8], length: usize) -> Vec<u8> {
+        let mut output = vec![0; length];
+        self.hash_into(message, &mut output);
+        output
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hash::Hash;
 
     #[test]
     // Test the creation of a new SHA-3 with 224 bits
     fn test_sha_3_new_224() {
-        let sha_3 = Sha3::new("password", 224);
+        let sha_3 = Sha3::new(224);
         assert_eq!(sha_3.b, 1152 + 2*224);
         assert_eq!(sha_3.c, 2*224);
         assert_eq!(sha_3.r, 1152);
@@ -156,23 +184,39 @@ mod tests {
     }
 
     #[test]
-    // Test the creation of a new SHA-3 with 256 bits
-    fn test_sha_3_new_256() {
-        let mut sha_3 = Sha3::new("password", 256);
-        let res = sha_3.sha_3();
-        println!("{:?}", res);
+    // Test SHA3-256 on the NIST examples
+    fn test_sha_3_256() {
+        let sha_3 = Sha3::new(256);
+        assert_eq!(Hash::new(sha_3.sha_3(b"")).to_string(), "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a");
+        assert_eq!(Hash::new(sha_3.sha_3(b"abc")).to_string(), "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+    }
+
+    #[test]
+    // Test SHA3-256 on the NIST examples of several blocks
+    fn test_sha_3_256_multi_block() {
+        let sha_3 = Sha3::new(256);
+        let check = |message: &[u8], expected: &str| assert_eq!(Hash::new(sha_3.sha_3(message)).to_string(), expected, "{} bytes", message.len());
+        // 1600 bits: one block of 136 bytes and 64 bytes
+        check(&[0xa3; 200], "79f38adec5c20307a98ef76e8324afbfd46cfd81b22e3973c65fa1bd9de31787");
+        check(b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+            "916f6061fe879741ca6469b43971dfdb28b1a32dc36cb3254e812be27aad1d18");
+        // One million "a"
+        check(&[b'a'; 1_000_000], "5c8875ae474a3634ba4fd55ec85bffd661f32aca75c6d699d0cdcb6c115891c1");
+        // The delimiter and the last bit of the padding in the same byte, and a block of padding only
+        check(&[0; 135], "7d080d7ba978a75c8a7d1f9be566c859084509c9c2b4928435c225d5777d98e3");
+        check(&[0; 136], "e772c9cf9eb9c991cdfcf125001b454fdbc0a95f188d1b4c844aa032ad6e075e");
     }
 
     #[test]
     fn test_sha3() {
-        let res = sha3_hash("password", None);
-        println!("{:?}", res);
+        let res = Hash::new(Sha3::new(256).sha_3(b"password"));
+        assert_eq!(res.to_string(), "c0067d4af4e87f00dbac63b6156828237059172d1bbeac67427345d6a9fda484");
     }
 
     #[test]
     // Test the creation of a new SHA-3 with 384 bits
     fn test_sha_3_new_384() {
-        let sha_3 = Sha3::new("password", 384);
+        let sha_3 = Sha3::new(384);
         assert_eq!(sha_3.b, 832 + 2*384);
         assert_eq!(sha_3.c, 2*384);
         assert_eq!(sha_3.r, 832);
@@ -182,7 +226,7 @@ mod tests {
     #[test]
     // Test the creation of a new SHA-3 with 512 bits
     fn test_sha_3_new_512() {
-        let sha_3 = Sha3::new("password", 512);
+        let sha_3 = Sha3::new(512);
         assert_eq!(sha_3.b, 576 + 2*512);
         assert_eq!(sha_3.c, 2*512);
         assert_eq!(sha_3.r, 576);
@@ -192,20 +236,142 @@ mod tests {
     // Test the creation of a new SHA-3 instance with a block size
     #[test]
     fn test_sha_3_new_with_block() {
-        let sha_3 = Sha3::new("password", 256);
+        let sha_3 = Sha3::new(256);
         assert_eq!(sha_3.b, 1088 + 2*256);
         assert_eq!(sha_3.c, 2*256);
         assert_eq!(sha_3.r, 1088);
         assert_eq!(sha_3.fingerprint, 256);
     }
 
-    // Test the creation of a new SHA-3 instance without a block size
+    // Test the creation of a new SHA-3 instance with an unknown fingerprint size
     #[test]
     fn test_sha_3_new_without_block() {
-        let sha_3 = Sha3::new("password", 256);
+        let sha_3 = Sha3::new(100);
         assert_eq!(sha_3.b, 1088 + 2*256);
         assert_eq!(sha_3.c, 2*256);
         assert_eq!(sha_3.r, 1088);
         assert_eq!(sha_3.fingerprint, 256);
     }
-}
\ No newline at end of file
+
+    // Test SHA3-224, SHA3-384 and SHA3-512 on the NIST examples (0 bit, "abc" and 1600 bits)
+    #[test]
+    fn test_sha_3_nist() {
+        let check = |fingerprint: i32, message: &[u8], expected: &str| {
+            let digest = Sha3::new(fingerprint).sha_3(message);
+            assert_eq!(digest.len(), fingerprint as usize / 8);
+            assert_eq!(Hash::new(digest).to_string(), expected, "SHA3-{} of {} bytes", fingerprint, message.len());
+        };
+        check(224, b"", "6b4e03423667dbb73b6e15454f0eb1abd4597f9a1b078e3f5b5a6bc7");
+        check(224, b"abc", "e642824c3f8cf24ad09234ee7d3c766fc9a3a5168d0c94ad73b46fdf");
+        check(224, &[0xa3; 200], "9376816aba503f72f96ce7eb65ac095deee3be4bf9bbc2a1cb7e11e0");
+        check(384, b"", "0c63a75b845e4f7d01107d852e4c2485c51a50aaaa94fc61995e71bbee983a2ac3713831264adb47fb6bd1e058d5f004");
+        check(384, b"abc", "ec01498288516fc926459f58e2c6ad8df9b473cb0fc08c2596da7cf0e49be4b298d88cea927ac7f539f1edf228376d25");
+        check(384, &[0xa3; 200], "1881de2ca7e41ef95dc4732b8f5f002b189cc1e42b74168ed1732649ce1dbcdd76197a31fd55ee989f2d7050dd473e8f");
+        check(512, b"", "a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859e0d1dcc1475c80a615b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26");
+        check(512, b"abc", "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0");
+        check(512, &[0xa3; 200], "e76dfad22084a8b1467fcf2ffa58361bec7628edf5f3fdc0e4805dc48caeeca81b7c13c30adf52a3659584739a2df46be589c51ca1a4a8416df6545a1ce8ba00");
+    }
+
+    // Test the in-house SHA-3 against the sha3 crate, for the four sizes and random messages
+    #[test]
+    fn test_sha_3_crate() {
+        use rand::Rng;
+        use sha3::{Digest, Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            // Up to 3 blocks of SHA3-224, the largest rate
+            let length = rng.gen_range(0..3 * 144);
+            let message: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+            assert_eq!(Sha3::new(224).sha_3(&message), Sha3_224::digest(&message).to_vec(), "length {}", length);
+            assert_eq!(Sha3::new(256).sha_3(&message), Sha3_256::digest(&message).to_vec(), "length {}", length);
+            assert_eq!(Sha3::new(384).sha_3(&message), Sha3_384::digest(&message).to_vec(), "length {}", length);
+            assert_eq!(Sha3::new(512).sha_3(&message), Sha3_512::digest(&message).to_vec(), "length {}", length);
+        }
+    }
+
+    // Compare the throughput of the in-house SHA3-256 with the sha3 crate on passwords
+    // Run with `cargo test --release -- --ignored --nocapture test_sha_3_speed`
+    #[test]
+    #[ignore]
+    fn test_sha_3_speed() {
+        use std::hint::black_box;
+        use std::time::Instant;
+        use sha3::{Digest, Sha3_256};
+        const HASHES: usize = 1_000_000;
+        let passwords: Vec<Vec<u8>> = (0..1000).map(|i| format!("pass{:04}", i).into_bytes()).collect();
+        let sha_3 = Sha3::new(256);
+        let mut digest = [0; 32];
+        let start = Instant::now();
+        for i in 0..HASHES {
+            sha_3.hash_into(black_box(&passwords[i % passwords.len()]), &mut digest);
+            black_box(&digest);
+        }
+        let in_house = start.elapsed();
+        let start = Instant::now();
+        for i in 0..HASHES {
+            black_box(Sha3_256::digest(black_box(&passwords[i % passwords.len()])));
+        }
+        let crate_sha3 = start.elapsed();
+        println!("in-house: {:.0} hashes/s, sha3 crate: {:.0} hashes/s", HASHES as f64 / in_house.as_secs_f64(), HASHES as f64 / crate_sha3.as_secs_f64());
+        assert!(in_house.as_secs_f64() < 3.0 * crate_sha3.as_secs_f64(), "in-house {:?}, sha3 crate {:?}", in_house, crate_sha3);
+    }
+
+    // Test SHAKE128 and SHAKE256 on the NIST examples, the 1600-bit message with 4096 bits of output
+    #[test]
+    fn test_shake_nist() {
+        let shake128 = Shake::new(128);
+        let shake256 = Shake::new(256);
+        assert_eq!((shake128.r, shake256.r), (1344, 1088));
+        assert_eq!(Hash::new(shake128.shake(b"", 32)).to_string(), "7f9c2ba4e88f827d616045507605853ed73b8093f6efbc88eb1a6eacfa66ef26");
+        assert_eq!(Hash::new(shake256.shake(b"", 64)).to_string(),
+            "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762fd75dc4ddd8c0f200cb05019d67b592f6fc821c49479ab48640292eacb3b7c4be");
+        // 512 bytes of output: 4 permutations of SHAKE128 (r = 168 bytes), 4 of SHAKE256 (r = 136 bytes)
+        let output = shake128.shake(&[0xa3; 200], 512);
+        assert_eq!(Hash::new(output[480..].to_vec()).to_string(), "44c9fb359fd56ac0a9a75a743cff6862f17d7259ab075216c0699511643b6439");
+        let output = shake256.shake(&[0xa3; 200], 512);
+        assert_eq!(Hash::new(output[480..].to_vec()).to_string(), "6a1a9d7846436e4dca5728b6f760eef0ca92bf0be5615e96959d767197a0beeb");
+    }
+
+    // Test SHAKE against the sha3 crate, for random messages and output lengths
+    #[test]
+    fn test_shake_crate() {
+        use rand::Rng;
+        use sha3::digest::{ExtendableOutput, Update, XofReader};
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let message: Vec<u8> = (0..rng.gen_range(0..400)).map(|_| rng.gen()).collect();
+            let length = rng.gen_range(0..600);
+            let mut expected = vec![0; length];
+            let mut shake = sha3::Shake128::default();
+            shake.update(&message);
+            shake.finalize_xof().read(&mut expected);
+            assert_eq!(Shake::new(128).shake(&message, length), expected);
+            let mut shake = sha3::Shake256::default();
+            shake.update(&message);
+            shake.finalize_xof().read(&mut expected);
+            assert_eq!(Shake::new(256).shake(&message, length), expected);
+        }
+    }
+
+    // Test the original Keccak on the test vectors of the Keccak team (0 bit and "abc") and against the sha3 crate
+    #[test]
+    fn test_keccak() {
+        use rand::Rng;
+        use sha3::{Digest, Keccak256, Keccak512};
+        let keccak_256 = Sha3::keccak(256);
+        let keccak_512 = Sha3::keccak(512);
+        assert_eq!((keccak_256.r, keccak_512.r), (1088, 576));
+        assert_eq!(Hash::new(keccak_256.sha_3(b"")).to_string(), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        assert_eq!(Hash::new(keccak_256.sha_3(b"abc")).to_string(), "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+        assert_eq!(Hash::new(keccak_512.sha_3(b"")).to_string(),
+            "0eab42de4c3ceb9235fc91acffe746b29c29a8c366b7c60e4e67c466f36a4304c00fa9caf9d87976ba469bcbe06713b435f091ef2769fb160cdab33d3670680e");
+        assert_eq!(Hash::new(keccak_512.sha_3(b"abc")).to_string(),
+            "18587dc2ea106b9a1563e32b3312421ca164c7f1f07bc922a9c83d77cea3a1e5d0c69910739025372dc14ac9642629379540c17e2a65b19d77aa511a9d00bb96");
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let message: Vec<u8> = (0..rng.gen_range(0..300)).map(|_| rng.gen()).collect();
+            assert_eq!(keccak_256.sha_3(&message), Keccak256::digest(&message).to_vec());
+            assert_eq!(keccak_512.sha_3(&message), Keccak512::digest(&message).to_vec());
+        }
+    }
+}
@@ -1,168 +1,179 @@
 //! Contains the implementation of the Keccak sponge function.
+//!
+//! The state of Keccak-f[1600] is an array of 25 lanes of 64 bits, the lane (x, y) being at the index x + 5y.
+//! The bytes of the message are absorbed into the lanes in little-endian order, as in FIPS 202,
+//! so the permutation never converts the message to bits.
 
-use std::ops::BitAnd;
+/// Number of lanes of the state.
+const LANES: usize = 25;
 
-use crate::hashage::Sha3;
+/// Size of the state in bytes (b = 1600 bits).
+pub const STATE_BYTES: usize = 8 * LANES;
 
-/// Keccak sponge function.  
-/// Here the default bit rate based on the fingerprint size:  
-/// 224 -> r = 1152  
-/// 256 -> r = 1088  
-/// 384 -> r = 832  
-/// 512 -> r = 576 
-/// 
+/// Number of rounds of Keccak-f[1600].
+const ROUNDS: usize = 24;
+
+/// Round constants of the ι routine.
+const ROUND_CONSTANTS: [u64; ROUNDS] = round_constants();
+
+/// Lanes visited by the π routine from the lane (1, 0).
+const PI: [usize; ROUNDS] = rho_pi().0;
+
+/// Rotations of the ρ routine of the lanes moved by the π routine.
+const RHO: [u32; ROUNDS] = rho_pi().1;
+
+/// Padding rules of the sponge, by the domain of the function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+    /// Original Keccak submission, before FIPS 202 (Ethereum): no domain bits
+    Keccak,
+    /// SHA-3 fixed-output functions: domain bits "01"
+    Sha3,
+    /// SHAKE extendable-output functions: domain bits "1111"
+    Shake,
+}
+
+impl Padding {
+    /// Get the domain bits followed by the first bit of the padding pad10*1, in little-endian order.
+    pub fn delimiter(self) -> u8 {
+        match self {
+            Padding::Keccak => 0x01,
+            Padding::Sha3 => 0x06,
+            Padding::Shake => 0x1f,
+        }
+    }
+}
+
+/// Compute the round constants with the linear feedback shift register of the specification.
+/// # Returns
+/// The constant XORed to the lane (0, 0) by the ι routine of each round.
+const fn round_constants() -> [u64; ROUNDS] {
+    let mut constants = [0; ROUNDS];
+    let mut r: u32 = 1;
+    let mut i = 0;
+    while i < ROUNDS {
+        // 0 <= j < 7
+        let mut j = 0;
+        while j < 7 {
+            // r = ((r << 1) XOR ((r >> 7) * 0x71)) mod 256
+            r = ((r << 1) ^ ((r >> 7) * 0x71)) % 256;
+            // If the second bit (from the right) of r is 1
+            if r & 2 == 2 {
+                constants[i] ^= 1 << ((1 << j) - 1);
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    constants
+}
+
+/// Compute the path of the ρ and π routines.
+/// # Returns
+/// The index of the lane reached at each step t, and the rotation (t + 1) * (t + 2) / 2 of the lane moved there.
+const fn rho_pi() -> ([usize; ROUNDS], [u32; ROUNDS]) {
+    let mut pi = [0; ROUNDS];
+    let mut rho = [0; ROUNDS];
+    let (mut x, mut y) = (1, 0);
+    let mut t = 0;
+    while t < ROUNDS {
+        // (x, y) = (y, (2 * x + 3 * y) mod 5)
+        (x, y) = (y, (2 * x + 3 * y) % 5);
+        pi[t] = x + 5 * y;
+        rho[t] = ((t + 1) * (t + 2) / 2 % 64) as u32;
+        t += 1;
+    }
+    (pi, rho)
+}
+
+/// Keccak sponge function.
+/// Here the default byte rate based on the fingerprint size:
+/// 224 -> r = 144
+/// 256 -> r = 136
+/// 384 -> r = 104
+/// 512 -> r = 72
+///
 /// # Arguments
-/// * `password` - The password to hash
-/// * `state` - The state array
-/// * `f` - The fingerprint
-/// * `r` - The bit rate
-/// * `nr` - The number of rounds
-/// * `r_iota` - The round index 
-#[derive(Debug)]
+/// * `state` - The state array, the lane (x, y) at the index x + 5y
+/// * `rate` - The byte rate (r / 8), a multiple of 8
+#[derive(Clone, Debug)]
 pub struct Keccak {
-    password: String,       // password
-    state: Vec<Vec<u64>>,   // state array
-    f: i32,                 // fingerprint
-    r: i32,                 // bit rate
-    nr: i32,                // number of rounds (by default 24)
-    r_iota: u32,            // round index
+    state: [u64; LANES],
+    rate: usize,
 }
 
-/// Implementation of the Keccak sponge function.  
-/// The Keccak sponge function is the main function of the Keccak algorithm.  
+/// Implementation of the Keccak sponge function.
+/// The Keccak sponge function is the main function of the Keccak algorithm.
 /// It is composed of two main steps:
-/// 1. Absorbing phase: The password is divided into 64-bit blocks, and each block is XORed with a lane of the state array.
-/// 2. Squeezing phase: The state array is updated after each block, and the state array is converted into a string.
+/// 1. Absorbing phase: The padded message is divided into blocks of r bytes, each block is XORed into the first lanes of the state,
+///    then the Keccak-f permutation is applied.
+/// 2. Squeezing phase: The output is read from the first lanes of the state, with a permutation between two blocks of r bytes.
 /// # Example
 /// ```rust
-/// let mut sha3 = Sha3::new("****", 256);
-/// sha3.preprocessing();
-/// let mut keccak = Keccak::new(&sha3);
-/// keccak.sponge();
-/// let res = keccak.state_to_strings();
-/// assert_eq!("9c75caf0e14b30ac6b50c5d2f464d3690a6c72890228dd4994b6dabaf261a2ad", res);
+/// let mut keccak = Keccak::new(136);
+/// keccak.absorb(b"****", Padding::Sha3);
+/// let mut digest = [0; 32];
+/// keccak.squeeze(&mut digest);
+/// assert_eq!(Hash::new(digest.to_vec()).to_string(), "9c75caf0e14b30ac6b50c5d2f464d3690a6c72890228dd4994b6dabaf261a2ad");
 /// ```
 /// # Note
-/// The sponge function is the main function of the Keccak algorithm.
+/// Nothing is allocated on the heap: the last block is padded in a buffer on the stack.
 impl Keccak {
 
-    /// Create a new Keccak instance.
+    /// Create a new Keccak instance with an empty state.
     /// # Arguments
-    /// * `obj` - A reference to a Sha3 instance
+    /// * `rate` - The byte rate, a multiple of 8 smaller than the state (r = b - c)
     /// # Returns
     /// A new Keccak instance.
-    pub fn new(obj: &Sha3) -> Keccak {
+    pub fn new(rate: usize) -> Keccak {
+        debug_assert!(rate > 0 && rate < STATE_BYTES && rate.is_multiple_of(8));
         Keccak {
-            password: obj.password_bytes.clone().to_string(),
-            state: vec![vec![0; 5]; 5],
-            f: obj.fingerprint,
-            r: obj.r,
-            nr: 24,
-            r_iota: 1,
+            state: [0; LANES],
+            rate,
         }
     }
 
-    /// Rotate left function.
-    /// # Arguments
-    /// * `a` - The number to rotate
-    /// * `n` - The number of bits to rotate
-    /// # Returns
-    /// The rotated number.
-    fn rol_64(a: u64, n: i32) -> u64 {
-        (a >> (64 - (n % 64))) + (a << (n % 64)) 
-    }
-
-    /// Fill the string with zeros if the length is less than the width.
-    /// # Example
-    /// ```
-    /// let num = "42";
-    /// let res = Keccak::zfill(num, 8);
-    /// assert_eq!(res, "00000042");
-    /// ```
-    /// # Arguments
-    /// * `s` - The string to fill
-    /// * `width` - The width of the string
-    /// # Returns
-    /// A string with zeros added.
-    fn zfill(s: &str, width: usize) -> String {
-        // If the length of the string is greater or equal than the width, we return the string
-        if width <= s.len() {
-            return s.to_string();
-        }
-        // Calculate the number of zeros to add
-        let padding = width - s.len();
-        let zeros: String = std::iter::repeat('0').take(padding).collect();
-        // Return the string with the zeros added
-        format!("{}{}", zeros, s)
-    }
-
-    /// Convert the state array into a string.
+    /// XOR a block into the first lanes of the state.
     /// # Arguments
     /// * `self` - The Keccak instance
-    /// # Returns
-    /// A string representing the state array, truncated to the fingerprint size divided by 4.
-    pub fn state_to_strings(&self) -> String {
-        // Number of lanes
-        let nlanes = self.r / self.f;
-        let mut output = String::new();
-        // 0 <= i < nlanes
-        for i in 0..nlanes {
-            let y = (i / 5) as usize;
-            let x = (i - 5 * y as i32) as usize;
-            // For each "lane" in the state, converts the value into a hexadecimal string, 
-            // inverts the byte order, adds leading zeros until the string is 16 characters wide,
-            // then adds this string to the output
-            output += Keccak::zfill(format!("{:08x}", self.state[x][y].swap_bytes()).as_str(), 16).as_str();
-            
+    /// * `block` - The block, of r bytes
+    fn xor_block(&mut self, block: &[u8]) {
+        for (lane, bytes) in self.state.iter_mut().zip(block.chunks_exact(8)) {
+            *lane ^= u64::from_le_bytes(bytes.try_into().unwrap());
         }
-        // Truncate the output to the fingerprint size divided by 4, and return it
-        output.truncate(self.f as usize / 4);
-        output
     }
 
     /// The θ routine performs a series of XOR operations and left rotations on the internal state of the Keccak algorithm.
     /// # Arguments
     /// * `self` - The Keccak instance
     fn routine_theta(&mut self) {
-        // Initialize two null vectors c and d of size 5
-        let mut c = vec![0; 5];
-        let mut d = vec![0; 5];
-
-        // 0 <= x < 5
-        for x in 0..5 {
-            // c[x] = state[x][0] XOR state[x][1] XOR state[x][2] XOR state[x][3] XOR state[x][4]
-            c[x] = self.state[x][0] ^ self.state[x][1] ^ self.state[x][2] ^ self.state[x][3] ^ self.state[x][4];
+        // c[x] = state[x][0] XOR state[x][1] XOR state[x][2] XOR state[x][3] XOR state[x][4]
+        let mut c = [0; 5];
+        for (x, c) in c.iter_mut().enumerate() {
+            *c = self.state[x] ^ self.state[x + 5] ^ self.state[x + 10] ^ self.state[x + 15] ^ self.state[x + 20];
         }
 
         // 0 <= x < 5
         for x in 0..5 {
             // d[x] = c[x-1] XOR rot(c[x+1], 1)
-            d[x] = c[(x + 4).rem_euclid(5)] ^ Keccak::rol_64(c[(x + 1).rem_euclid(5)], 1);
-        }
-
-        // 0 <= x < 5
-        for x in 0..5 {
-            // 0 <= y < 5
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            // 0 <= y < 5: state[x][y] = state[x][y] XOR d[x]
             for y in 0..5 {
-                // state[x][y] = state[x][y] XOR d[x]
-                self.state[x][y] ^= d[x];
+                self.state[x + 5 * y] ^= d;
             }
         }
     }
-    
+
     /// The ρ and π routines perform a series of permutations and rotations to the left on the internal state of the Keccak algorithm.
     /// # Arguments
     /// * `self` - The Keccak instance
     fn routine_rho_pi(&mut self) {
-        // Initialize the position (x, y) to (1, 0) and the current value to state[1][0]
-        let (mut x, mut y) = (1, 0);
-        let mut current = self.state[x][y];
+        // The current value starts at state[1][0]
+        let mut current = self.state[1];
         // 0 <= t <= 23
-        for t in 0..=23 {
-            // (x, y) = (y, (2 * x + 3 * y) mod 5)
-            (x, y) = (y, (2 * x + 3 * y).rem_euclid(5));
+        for t in 0..ROUNDS {
             // (current, state[x][y]) = (state[x][y], rot(current, (t + 1) * (t + 2) / 2))
-            (current, self.state[x][y]) = (self.state[x][y], Keccak::rol_64(current, ((t + 1) * (t + 2) / 2) as i32));
+            (current, self.state[PI[t]]) = (self.state[PI[t]], current.rotate_left(RHO[t]));
         }
     }
 
@@ -171,89 +182,81 @@ impl Keccak {
     /// * `self` - The Keccak instance
     fn routine_chi(&mut self) {
         // 0 <= y < 5
-        for y in 0..5 {
-            let mut s = vec![0; 5]; // Initialize a null vector s of size 5
-            // 0 <= x < 5
+        for row in self.state.chunks_exact_mut(5) {
+            let s = [row[0], row[1], row[2], row[3], row[4]];
+            // state[x][y] = s[x] XOR (NOT s[(x + 1) mod 5] AND s[(x + 2) mod 5])
             for x in 0..5 {
-                s[x] = self.state[x][y];
-            }
-
-            // 0 <= x < 5
-            for x in 0..5 {
-                // state[x][y] = s[x] XOR (NOT s[(x + 1) mod 5] AND s[(x + 2) mod 5])
-                self.state[x][y] = s[x] ^ ((!s[(x + 1).rem_euclid(5)]) & s[(x + 2).rem_euclid(5)]);
+                row[x] = s[x] ^ (!s[(x + 1) % 5] & s[(x + 2) % 5]);
             }
         }
     }
-    
-    /// The ι routines perform a series of rotation, XOR and shift operations on the internal state of the Keccak algorithm.
+
+    /// The ι routine XORs the constant of the round into the lane (0, 0).
     /// # Arguments
     /// * `self` - The Keccak instance
-    fn routine_iota(&mut self) {
-        // 0 <= j < 7
-        for j in 0..7 {
-            // r_iota = ((r_iota << 1) XOR ((r_iota >> 7) * 0x71)) mod 256
-            self.r_iota = ((self.r_iota << 1) ^ ((self.r_iota >> 7) * 0x71)).rem_euclid(256);
-            // If the second bit (from the right) of self.r_iota is 1
-            if self.r_iota.bitand(2) == 2 {
-                self.state[0][0] ^= 1 << ((1 << j) - 1);
-            }
-        }
-
+    /// * `round` - The index of the round
+    fn routine_iota(&mut self, round: usize) {
+        self.state[0] ^= ROUND_CONSTANTS[round];
     }
-    
-    /// The round index method applies the θ, ρ, π, χ and ι routines to the internal state of the Keccak algorithm.
+
+    /// The Keccak-f[1600] permutation applies the θ, ρ, π, χ and ι routines to the state for 24 rounds.
     /// # Arguments
     /// * `self` - The Keccak instance
-    fn round_index(&mut self) {
-        // Apply the θ, ρ, π, χ and ι routines
-        self.routine_theta();
-        self.routine_rho_pi();
-        self.routine_chi();
-        self.routine_iota();
+    pub fn keccak_f(&mut self) {
+        // 0 <= round < 24
+        for round in 0..ROUNDS {
+            self.routine_theta();
+            self.routine_rho_pi();
+            self.routine_chi();
+            self.routine_iota(round);
+        }
     }
 
-    /// The Keccak-p permutation loops over the round index method for a number of rounds (24 by default).
+    /// Absorb a whole message with its padding.
     /// # Arguments
     /// * `self` - The Keccak instance
+    /// * `message` - The message, of any length
+    /// * `padding` - The padding rule of the function
     /// # Note
-    /// It is composed of 24 rounds.  
-    /// Each round consists of the θ, ρ, π, χ and ι routines (round_index method).  
-    /// The permutation is applied to the state array.  
-    /// The state array is updated after each round.
-    fn keccak_p(&mut self) {
-        // 0 <= i < nr (24 by default)
-        for _ in 0..self.nr {
-            self.round_index(); // Apply the θ, ρ, π, χ and ι routines
+    /// The full blocks of the message are absorbed in place, then the rest of the message is padded in a last block.
+    /// The padding pad10*1 adds the delimiter of the padding rule after the message, zeros, and a last bit 1 at the end of the block:
+    /// when the rest fills the block but one byte, the delimiter and the last bit share this byte,
+    /// and when the message is a multiple of the rate, the last block only contains the padding.
+    pub fn absorb(&mut self, message: &[u8], padding: Padding) {
+        let mut blocks = message.chunks_exact(self.rate);
+        for block in blocks.by_ref() {
+            self.xor_block(block);
+            self.keccak_f();
         }
+        // Last block, shorter than the rate (possibly empty)
+        let rest = blocks.remainder();
+        let mut block = [0u8; STATE_BYTES];
+        block[..rest.len()].copy_from_slice(rest);
+        block[rest.len()] ^= padding.delimiter();
+        block[self.rate - 1] ^= 0x80;
+        self.xor_block(&block[..self.rate]);
+        self.keccak_f();
     }
-    
-    /// The sponge function absorbs the input data into the internal state of the Keccak algorithm, performs a series of operations on this state, then applies the Keccak-p permutation.
+
+    /// Squeeze the output from the state.
     /// # Arguments
     /// * `self` - The Keccak instance
+    /// * `output` - The buffer filled with the output, of any length
     /// # Note
-    /// The sponge function is the main function of the Keccak algorithm.
-    pub fn sponge(&mut self) {
-        let r_octet = self.r as usize / 8;  // Transform the bit rate into a byte rate
-        let n_lanes = r_octet / 8;          // Number of lanes
-        // 0 <= i < n_lanes
-        for i in 0..n_lanes {
-            let mut lane = String::new(); // Initialize a mutable string lane
-            // 0 <= j < 8
-            for j in 0..8 {
-                // Extracts a substring from self.password based on the values of i and j
-                // Concatenates it with the current value of lane, and assigns the result to lane
-                lane = format!("{}{}",&self.password[(i * 64 + j * 8)..(i * 64 + j * 8 + 8)], lane);
+    /// The output is read r bytes at a time from the first bytes of the state, the lanes in little-endian order,
+    /// and the Keccak-f permutation is applied between two blocks of output (extendable-output functions like SHAKE).
+    /// The last lane can be partly read (SHA3-224 reads 3.5 lanes).
+    /// The digests of SHA-3 always fit in the rate (64 bytes of SHA3-512 for r = 72 bytes), so they need a single block.
+    pub fn squeeze(&mut self, output: &mut [u8]) {
+        let mut blocks = output.chunks_mut(self.rate).peekable();
+        while let Some(block) = blocks.next() {
+            for (bytes, lane) in block.chunks_mut(8).zip(self.state.iter()) {
+                bytes.copy_from_slice(&lane.to_le_bytes()[..bytes.len()]);
+            }
+            if blocks.peek().is_some() {
+                self.keccak_f();
             }
-
-            // Initializes y based on the value of i, and x based on the value of i and y
-            let y = i / 5;
-            let x = i - 5 * y;
-            self.state[x][y] ^= u64::from_str_radix(&lane, 2).unwrap() as u64;
         }
-
-        // Apply the Keccak-p permutation
-        self.keccak_p();
     }
 }
 
@@ -261,23 +264,44 @@ impl Keccak {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hash::Hash;
+
+    #[test]
+    /// Test the constants computed from the specification
+    fn test_constants() {
+        assert_eq!(ROUND_CONSTANTS[0], 0x0000000000000001);
+        assert_eq!(ROUND_CONSTANTS[1], 0x0000000000008082);
+        assert_eq!(ROUND_CONSTANTS[23], 0x8000000080008008);
+        assert_eq!(PI[..4], [10, 7, 11, 17]);
+        assert_eq!(RHO[..4], [1, 3, 6, 10]);
+    }
 
     #[test]
-    /// Test the `zfill` method
-    fn test_zfill() {
-        let num = "42";
-        let res = Keccak::zfill(num, 8);
-        assert_eq!(res, "00000042");
+    /// Test the permutation of the zero state (Keccak team's KeccakF-1600 intermediate values)
+    fn test_keccak_f() {
+        let mut keccak = Keccak::new(136);
+        keccak.keccak_f();
+        assert_eq!(keccak.state[0], 0xF1258F7940E1DDE7);
+        assert_eq!(keccak.state[24], 0xEAF1FF7B5CECA249);
     }
-    
+
     #[test]
-    /// Test the creation of a new Keccak instance
+    /// Test the sponge with the original Keccak padding (Keccak-256 of "", used by Ethereum)
+    fn test_keccak_padding() {
+        let mut keccak = Keccak::new(136);
+        keccak.absorb(b"", Padding::Keccak);
+        let mut digest = [0; 32];
+        keccak.squeeze(&mut digest);
+        assert_eq!(Hash::new(digest.to_vec()).to_string(), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+    }
+
+    #[test]
+    /// Test the sponge with the SHA-3 padding
     fn test_sha_3_keccak() {
-        let mut sha3 = Sha3::new("****", 256);
-        sha3.preprocessing();
-        let mut keccak = Keccak::new(&sha3);
-        keccak.sponge();
-        let res = keccak.state_to_strings();
-        assert_eq!("9c75caf0e14b30ac6b50c5d2f464d3690a6c72890228dd4994b6dabaf261a2ad", res);
+        let mut keccak = Keccak::new(136);
+        keccak.absorb(b"****", Padding::Sha3);
+        let mut digest = [0; 32];
+        keccak.squeeze(&mut digest);
+        assert_eq!(Hash::new(digest.to_vec()).to_string(), "9c75caf0e14b30ac6b50c5d2f464d3690a6c72890228dd4994b6dabaf261a2ad");
     }
-}
\ No newline at end of file
+}
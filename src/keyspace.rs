@@ -0,0 +1,242 @@
+//! Set of the passwords covered by a rainbow table: every password of the charset
+//! with a length between a minimum and a maximum length, or every password of a mask.
+//!
+//! The passwords are numbered by their keyspace index: the shortest passwords first,
+//! then the passwords of a same length in the order of their value, each position being
+//! a digit in the base of its charset (`charset.len()`, or the size of the charset of the position in a mask).
+
+use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::charset::Charset;
+use crate::mask::Mask;
+
+/// The passwords of a rainbow table.
+/// # Fields
+/// * `charset` - The alphabet of the passwords (with a mask, every character of the mask)
+/// * `min_length` - The length of the shortest passwords
+/// * `max_length` - The length of the longest passwords
+/// * `mask` - The mask giving the charset of each position, if any
+/// * `size` - The number of passwords
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keyspace {
+    pub charset: Arc<Charset>,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub mask: Option<Mask>,
+    size: u128,
+}
+
+impl Keyspace {
+    /// Create a keyspace.
+    /// # Arguments
+    /// * `charset` - The alphabet of the passwords
+    /// * `min_length` - The length of the shortest passwords
+    /// * `max_length` - The length of the longest passwords
+    /// # Returns
+    /// The keyspace, or an error if the lengths are invalid or if the passwords cannot be numbered on 128 bits.
+    pub fn new(charset: Arc<Charset>, min_length: usize, max_length: usize) -> Result<Keyspace, String> {
+        if min_length == 0 || min_length > max_length {
+            return Err(format!("Invalid password lengths: {} to {}", min_length, max_length));
+        }
+        if max_length > u8::MAX as usize {
+            return Err(format!("Passwords cannot be longer than {} characters", u8::MAX));
+        }
+        let size = (min_length..=max_length).try_fold(0u128, |size, length| {
+            charset.keyspace(length).and_then(|passwords| size.checked_add(passwords))
+        }).ok_or(format!("{} passwords of {} to {} characters do not fit in 128 bits", charset.len(), min_length, max_length))?;
+        Ok(Keyspace { charset, min_length, max_length, mask: None, size })
+    }
+
+    /// Create the keyspace of a mask.
+    /// # Arguments
+    /// * `mask` - The mask giving the charset of each position
+    /// # Returns
+    /// The keyspace, or an error if the passwords cannot be numbered on 128 bits.
+    pub fn from_mask(mask: Mask) -> Result<Keyspace, String> {
+        let length = mask.positions().len();
+        if length > u8::MAX as usize {
+            return Err(format!("Passwords cannot be longer than {} characters", u8::MAX));
+        }
+        let size = mask.positions().iter().try_fold(1u128, |size, charset| size.checked_mul(charset.len() as u128))
+            .ok_or(format!("The passwords of the mask {} do not fit in 128 bits", mask))?;
+        // Every character of the mask, in the order of their first position
+        let mut chars = String::new();
+        for c in mask.positions().iter().flat_map(|charset| charset.to_string().chars().collect::<Vec<char>>()) {
+            if !chars.contains(c) {
+                chars.push(c);
+            }
+        }
+        Ok(Keyspace { charset: Arc::new(Charset::new(&chars)?), min_length: length, max_length: length, mask: Some(mask), size })
+    }
+
+    /// Create a keyspace of passwords of a single length.
+    /// # Arguments
+    /// * `charset` - The alphabet of the passwords
+    /// * `length` - The length of the passwords
+    /// # Returns
+    /// The keyspace, or an error if the length is invalid or if the passwords cannot be numbered on 128 bits.
+    pub fn fixed(charset: Arc<Charset>, length: usize) -> Result<Keyspace, String> {
+        Keyspace::new(charset, length, length)
+    }
+
+    /// Get the number of passwords.
+    pub fn size(&self) -> u128 {
+        self.size
+    }
+
+    /// Check if all the passwords have the same length.
+    pub fn is_fixed(&self) -> bool {
+        self.min_length == self.max_length
+    }
+
+    /// Get the charset of a position of the passwords.
+    pub fn position(&self, position: usize) -> &Charset {
+        match &self.mask {
+            Some(mask) => &mask.positions()[position],
+            None => &self.charset,
+        }
+    }
+
+    /// Get the average length of the passwords, each password counting once.
+    pub fn mean_length(&self) -> f64 {
+        (self.min_length..=self.max_length).map(|length| self.passwords(length) as f64 * length as f64).sum::<f64>() / self.size as f64
+    }
+
+    /// Get the number of passwords of a length.
+    fn passwords(&self, length: usize) -> u128 {
+        match &self.mask {
+            Some(_) => self.size,
+            None => self.charset.keyspace(length).unwrap(),
+        }
+    }
+
+    /// Get the keyspace index of the first password of a length.
+    fn offset(&self, length: usize) -> u128 {
+        (self.min_length..length).map(|l| self.passwords(l)).sum()
+    }
+
+    /// Find the length of the password of a keyspace index.
+    /// # Arguments
+    /// * `index` - The keyspace index, lower than `size()`
+    /// # Returns
+    /// The length of the password and its index among the passwords of this length.
+    pub fn locate(&self, index: u128) -> (usize, u128) {
+        let mut index = index;
+        for length in self.min_length..self.max_length {
+            let passwords = self.passwords(length);
+            if index < passwords {
+                return (length, index);
+            }
+            index -= passwords;
+        }
+        (self.max_length, index)
+    }
+
+    /// Get the password of a keyspace index.
+    /// # Arguments
+    /// * `index` - The keyspace index, lower than `size()`
+    pub fn password(&self, index: u128) -> String {
+        let mut password = Vec::new();
+        self.password_into(index, &mut password);
+        password.into_iter().map(char::from).collect()
+    }
+
+    /// Write the password of a keyspace index in a buffer, without allocation once the buffer is long enough.
+    /// # Arguments
+    /// * `index` - The keyspace index, lower than `size()`
+    /// * `password` - The buffer receiving the password, the characters of the charsets being ASCII
+    pub fn password_into(&self, index: u128, password: &mut Vec<u8>) {
+        let (length, mut index) = self.locate(index);
+        password.clear();
+        password.resize(length, 0);
+        for (i, c) in password.iter_mut().enumerate().rev() {
+            let charset = self.position(i);
+            let base = charset.len() as u128;
+            *c = charset.char((index % base) as u64) as u8;
+            index /= base;
+        }
+    }
+
+    /// Get the keyspace index of a password.
+    /// # Returns
+    /// The keyspace index, None if the password is not in the keyspace.
+    pub fn index(&self, password: &str) -> Option<u128> {
+        let length = password.chars().count();
+        if length < self.min_length || length > self.max_length {
+            return None;
+        }
+        let index = password.chars().enumerate().try_fold(0, |acc, (i, c)| {
+            let charset = self.position(i);
+            charset.value(c).map(|value| acc * charset.len() as u128 + value as u128)
+        })?;
+        Some(self.offset(length) + index)
+    }
+}
+
+/// Display the lengths and the charset, or the mask of the keyspace.
+impl Display for Keyspace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(mask) = &self.mask {
+            write!(f, "passwords of the mask {}", mask)
+        } else if self.is_fixed() {
+            write!(f, "{}-character passwords of {}", self.max_length, self.charset)
+        } else {
+            write!(f, "{} to {}-character passwords of {}", self.min_length, self.max_length, self.charset)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size() {
+        let digits = Arc::new(Charset::preset("digits").unwrap());
+        assert_eq!(Keyspace::fixed(digits.clone(), 4).unwrap().size(), 10_000);
+        assert_eq!(Keyspace::new(digits.clone(), 1, 4).unwrap().size(), 11_110);
+        assert!(Keyspace::new(digits.clone(), 5, 4).is_err());
+        assert!(Keyspace::new(digits.clone(), 0, 4).is_err());
+        assert!(Keyspace::new(digits.clone(), 1, 40).is_err());
+        assert!(Keyspace::new(Arc::new(Charset::preset("base64").unwrap()), 1, 20).is_ok());
+        assert!((Keyspace::new(digits, 1, 2).unwrap().mean_length() - 210.0 / 110.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_index() {
+        let keyspace = Keyspace::new(Arc::new(Charset::preset("digits").unwrap()), 1, 3).unwrap();
+        assert_eq!(keyspace.password(0), "0");
+        assert_eq!(keyspace.password(9), "9");
+        assert_eq!(keyspace.password(10), "00");
+        assert_eq!(keyspace.password(110), "000");
+        assert_eq!(keyspace.password(1109), "999");
+        assert_eq!(keyspace.locate(57), (2, 47));
+        // The buffer is reused for a shorter password
+        let mut buffer = b"12345".to_vec();
+        keyspace.password_into(57, &mut buffer);
+        assert_eq!(buffer, b"47");
+        for index in 0..keyspace.size() {
+            assert_eq!(keyspace.index(&keyspace.password(index)), Some(index));
+        }
+        assert_eq!(keyspace.index("0000"), None);
+        assert_eq!(keyspace.index("0a"), None);
+    }
+
+    #[test]
+    fn test_mask() {
+        let keyspace = Keyspace::from_mask(Mask::new("?u?d!", &[]).unwrap()).unwrap();
+        assert_eq!(keyspace.size(), 260);
+        assert_eq!(keyspace.charset.len(), 37);
+        assert_eq!(keyspace.password(0), "A0!");
+        assert_eq!(keyspace.password(11), "B1!");
+        assert_eq!(keyspace.password(259), "Z9!");
+        for index in 0..keyspace.size() {
+            assert_eq!(keyspace.index(&keyspace.password(index)), Some(index));
+        }
+        assert_eq!(keyspace.index("a0!"), None);
+        assert_eq!(keyspace.index("A0?"), None);
+        assert!(Keyspace::from_mask(Mask::new(&"?a".repeat(19), &[]).unwrap()).is_ok());
+        assert!(Keyspace::from_mask(Mask::new(&"?a".repeat(20), &[]).unwrap()).is_err());
+    }
+}
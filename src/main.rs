@@ -5,7 +5,7 @@
 //! ```
 //! 
 //! ```rust
-//! cargo run -- search --hash 2a4c83e680475c86a7b6ccb40a7b113e9a5da06af47852b72fbf1a84614dcb69 -l 4
+//! cargo run -- search --hash 2a4c83e680475c86a7b6ccb40a7b113e9a5da06af47852b72fbf1a84614dcb69
 //! ```
 //! 
 //! *`Remark:` Note that there are two - between run and the command.*
@@ -13,18 +13,70 @@
 //! # Some options are available for :
 //! *Generation command:*
 //! 
-//! -m: Use memory file.
+//! -m: Resume the generation from the checkpoint of the table (checkpoint.txt).
 //! 
 //! -c: Chain length.
 //! 
-//! -l: Password length.
+//! --distinguished-bits: End the chains at distinguished points (keyspace index with this number of low bits at zero), -c is then the maximum chain length.
+//! 
+//! -l: Password length (maximum length with --min-length, also available as --max-length).
+//! 
+//! --min-length: Minimum password length, the table covers every length from --min-length to -l.
+//! 
+//! --tables: Number of tables, each with its own reduction function (the search queries all of them).
+//! 
+//! -f: Format of the table files (binary or text).
+//! 
+//! --perfect: Remove the merged chains when the generation stops.
+//! 
+//! -t: Hash algorithm (md5, sha1, sha256, ntlm, sha3-224, sha3-256, sha3-384, sha3-512, shake128, shake256, keccak-256, keccak-512).
+//! 
+//! --threads: Number of threads (default the number of CPUs). A new table is split in one file per thread,
+//! the number of files is recorded in the manifest and does not depend on the threads of a resumed generation or of the search.
+//! 
+//! -q: Quiet mode, the progress (chains per second, start points used, bytes written and time left) is not printed.
+//! 
+//! --chains, --coverage, --time, --max-bytes: Stop conditions, the generation stops cleanly at the first one reached
+//! (number of chains of each table, fraction of the start points used, duration like 6h, size of the table files like 10G)
+//! and can be resumed from its checkpoint.
+//! 
+//! -s: Charset of the passwords, a preset (digits, lower, upper, lower-digits, mixalpha-numeric, base64, ascii) or the characters themselves.
+//! 
+//! --mask: Hashcat-style mask giving the charset of each position (?l ?u ?d ?s ?a ?h ?H ?1 ?2 ?3 ?4 ??), replaces -l, --min-length and -s.
+//! 
+//! -1, -2, -3, -4: Custom charsets of the mask.
+//! 
+//! *Estimate command (have the -c, --tables, -l, --min-length, -s and --mask options of the generation command and the following option):*
+//! 
+//! -m: Number of chains of each table.
+//! 
+//! *Plan command (have the -t, -f, -l, --min-length, -s and --mask options of the generation command and the following options):*
+//! 
+//! --success: Target success probability (default 0.95).
+//! 
+//! -d: Disk budget (like 500M or 10G).
+//! 
+//! --time: Maximum search time per hash in seconds (default 1).
+//! 
+//! --threads: Number of threads of the generation (default the number of CPUs), used for the generation time.
+//! 
+//! *Sort command:*
+//! 
+//! -r: Number of chains sorted in memory at once.
+//! 
+//! --perfect: Only keep one chain per endpoint.
 //! 
 //! *Search command (have the same options as the generation command and the following options):*
 //! 
+//! -c, -l, --min-length, -t and -s are read from the manifest written by the generation. If they are given, they must match it.
+//! 
 //! -hash: Hash to search.
 //! 
 //! -hashs_path: Path to the file containing the hashs to search.
 //! 
+//! -q: Quiet mode, only the passwords found and the hashs not found are printed (no progress of the precomputation,
+//! of the bytes scanned, of the false alarms and of the hashs found).
+//! 
 //! # Examples
 //! ```rust
 //! cargo run -- generation
@@ -41,11 +93,48 @@
 //! ```rust
 //! cargo run -- generation -m false
 //! ```
-//! The code above will generate a rainbow table without using the memory file.
+//! The code above will generate a rainbow table without resuming from the checkpoint.
 //! ```rust
 //! cargo run -- generation -m false -c 50 -l 4
 //! ```
-//! The code above will generate a rainbow table without using the memory file, with a chain length of 50 and a password length of 4.
+//! The code above will generate a rainbow table without resuming from the checkpoint, with a chain length of 50 and a password length of 4.
+//! ```rust
+//! cargo run -- generation -l 8 -s digits
+//! ```
+//! The code above will generate a rainbow table of 8-digit PIN codes.
+//! ```rust
+//! cargo run -- generation --min-length 1 --max-length 6 -s lower
+//! ```
+//! The code above will generate a single rainbow table for the lowercase passwords of 1 to 6 characters.
+//! ```rust
+//! cargo run -- generation --mask '?u?l?l?l?l?d?d!'
+//! ```
+//! The code above will generate a rainbow table for the passwords like `Hello42!`.
+//! ```rust
+//! cargo run -- generation -l 5 --tables 4
+//! ```
+//! The code above will generate 4 tables with different reduction functions in the same folder, to find more passwords.
+//! ```rust
+//! cargo run -- generation -c 1000 --distinguished-bits 6
+//! ```
+//! The code above will generate a table whose chains end at distinguished points (64 passwords long on average),
+//! the search then computes a single chain per hash instead of one per column.
+//! ```rust
+//! cargo run -- estimate -l 6 -s lower -m 1000000 -c 1000 --tables 4
+//! ```
+//! The code above will print the success probability, the size and the search cost of 4 tables of one million chains
+//! of length 1000 for the 6-letter lowercase passwords, without generating them.
+//! ```rust
+//! cargo run -- plan -l 6 -s lower -d 100M --time 0.5
+//! ```
+//! The code above will measure the hash speed of the machine and propose the chain length, the number of chains
+//! and the number of tables finding 95% of the 6-letter lowercase passwords with 100 MiB of tables and half a second per hash,
+//! with the generation command to copy.
+//! 
+//! ```rust
+//! cargo run -- sort
+//! ```
+//! The code above will sort the rainbow table by endpoint, so the search uses binary searches instead of reading the whole table.
 //! 
 //! Note that options can be combined.
 
@@ -53,15 +142,36 @@ mod password;
 mod reduction;
 mod hash;
 mod hashage;
+mod algorithm;
+mod charset;
+mod keyspace;
+mod mask;
 mod generation;
 mod keccak;
-mod sha3;
-use generation::generation_main;
+mod table;
+mod manifest;
+mod sort;
+mod checkpoint;
+mod estimate;
+use estimate::estimate_main;
+mod plan;
+mod progress;
+use progress::parse_duration;
+use plan::{parse_size, plan_main};
+use generation::{generation_main, StopConditions};
 mod search;
 use search::search_main;
+use sort::{sort_main, RUN_CHAINS};
+use table::TableFormat;
+use algorithm::HashType;
+use charset::{Charset, DEFAULT_CHARSET};
+use keyspace::Keyspace;
+use mask::Mask;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 
 #[derive(Parser)]
@@ -80,12 +190,14 @@ enum Commands {
         // Path for the output file, default is ./output/
         path: Option<PathBuf>,
 
-        #[clap(long, short = 'm', default_value = "true")]
-        /// Use memory file
-        /// If the memory file exists, use it to generate the rainbow table
-        /// from the last password in the memory file
-        /// If the memory file does not exist, generate the rainbow table
-        /// and store the last password if the program is stopped
+        #[clap(long, short = 'm', default_value = "true", default_missing_value = "true", num_args = 0..=1, action = clap::ArgAction::Set)]
+        /// Resume from the checkpoint
+        /// If the checkpoint exists, resume the generation of each table file
+        /// from its next start point
+        /// When the program is stopped, the next start points are written
+        /// in the checkpoint
+        /// Without the checkpoint (-m false, or no checkpoint.txt), the table must not have chains yet:
+        /// a table with chains is an error, remove its folder to generate it again
         /// Default is true
         use_mem: bool,
 
@@ -94,15 +206,132 @@ enum Commands {
         /// Chain length must be between 1 and 2048
         /// Default is 100
         /// Chain length is the number of reductions to perform
-        /// before storing the password in the memory file
+        /// before storing the password in the table
         /// The higher the chain length is, the less memory is used
         /// but the longer it takes to retrieve a password
         /// The lower the chain length is, the more memory is used
         /// but the faster it is to retrieve a password
         chain_length: u16,
 
-        #[clap(long, short = 'l', default_value = "7")]
-        password_length: usize,
+        #[clap(long, default_value = "0")]
+        /// Number of low bits at zero of the distinguished points
+        /// The chains end at the first password whose keyspace index is a distinguished point,
+        /// the chain length is then their maximum length
+        /// A distinguished point is expected every 2^bits passwords
+        /// Default is 0: chains of chain length passwords
+        distinguished_bits: u8,
+
+        #[clap(long, default_value = "1")]
+        /// Number of tables
+        /// Each table has its own reduction function, several tables find more passwords
+        /// Default is 1
+        tables: u16,
+
+        #[clap(long, short = 'f', value_enum, default_value = "binary")]
+        /// Format of the table files
+        /// binary: fixed-width records (smaller and faster to search)
+        /// text: one line per chain
+        format: TableFormat,
+
+        #[clap(long)]
+        /// Build a perfect table when the generation stops
+        /// The chains are sorted by endpoint and only one chain is kept per endpoint
+        perfect: bool,
+
+        #[clap(long, short = 't', value_enum, default_value = "sha3-256")]
+        /// Hash algorithm of the table
+        hash_type: HashType,
+
+        #[clap(long)]
+        /// Number of threads
+        /// Default is the number of CPUs
+        /// A new table is split in one file per thread, a resumed table keeps its files
+        threads: Option<usize>,
+
+        #[clap(long, short = 'q')]
+        /// Do not print the progress of the generation
+        quiet: bool,
+
+        #[clap(long)]
+        /// Stop when each table has this number of chains
+        chains: Option<u64>,
+
+        #[clap(long)]
+        /// Stop when this fraction of the start points is used, between 0 and 1
+        coverage: Option<f64>,
+
+        #[clap(long, value_parser = parse_duration)]
+        /// Stop after this time, in seconds or with units (like 90s, 45m, 6h or 1h30m)
+        time: Option<Duration>,
+
+        #[clap(long, value_parser = parse_size)]
+        /// Stop before the table files exceed this size, in bytes or with a unit (K, M, G, T or KB, MB, GB, TB)
+        max_bytes: Option<u64>,
+
+        #[command(flatten)]
+        keyspace: KeyspaceArgs,
+    },
+    /// Estimate the success probability, the size and the search cost of rainbow tables before generating them
+    Estimate {
+        #[clap(long, short = 'm')]
+        /// Number of chains of each table
+        chains: u64,
+
+        #[clap(long, short = 'c', default_value = "100")]
+        /// Chain length
+        chain_length: u16,
+
+        #[clap(long, default_value = "1")]
+        /// Number of tables
+        tables: u16,
+
+        #[command(flatten)]
+        keyspace: KeyspaceArgs,
+    },
+    /// Propose the chain length, the number of chains and the number of tables reaching a success probability
+    Plan {
+        #[clap(long, default_value = "0.95")]
+        /// Target success probability, between 0 and 1
+        success: f64,
+
+        #[clap(long, short = 'd', value_parser = parse_size)]
+        /// Disk budget, in bytes or with a unit (K, M, G, T or KB, MB, GB, TB)
+        disk: u64,
+
+        #[clap(long, default_value = "1")]
+        /// Maximum search time per hash, in seconds
+        time: f64,
+
+        #[clap(long, short = 't', value_enum, default_value = "sha3-256")]
+        /// Hash algorithm of the tables, its speed is measured on this machine
+        hash_type: HashType,
+
+        #[clap(long, short = 'f', value_enum, default_value = "binary")]
+        /// Format of the table files
+        format: TableFormat,
+
+        #[clap(long)]
+        /// Number of threads of the generation, used for the generation time
+        /// Default is the number of CPUs
+        threads: Option<usize>,
+
+        #[command(flatten)]
+        keyspace: KeyspaceArgs,
+    },
+    /// Sort the rainbow table by endpoint to speed up the search
+    Sort {
+        #[clap(default_value = "./output/")]
+        // Path for the table folder, default is ./output/
+        path: PathBuf,
+
+        #[clap(long, short = 'r', default_value_t = RUN_CHAINS)]
+        /// Number of chains sorted in memory at once
+        /// Larger tables are sorted in several runs merged on disk
+        run_chains: usize,
+
+        #[clap(long)]
+        /// Only keep one chain per endpoint (perfect table)
+        perfect: bool,
     },
     /// Search for a password in the rainbow table
     Search {
@@ -110,8 +339,10 @@ enum Commands {
         // Path for the input folder, default is ./output/
         path: PathBuf,
 
-        #[clap(long, short = 'c', default_value = "100")]
-        chain_length: u16,
+        #[clap(long, short = 'c')]
+        /// Chain length
+        /// Read from the manifest of the table if not given
+        chain_length: Option<u16>,
 
         #[clap(long)]
         hash: Option<String>,
@@ -119,34 +350,164 @@ enum Commands {
         #[clap(long, short = 'p')]
         hashs_path: Option<PathBuf>,
         
-        #[clap(long, short = 'l')]
-        password_length: usize,
+        #[clap(long, short = 'l', visible_alias = "max-length")]
+        /// Password length
+        /// Read from the manifest of the table if not given
+        password_length: Option<usize>,
+
+        #[clap(long)]
+        /// Minimum password length
+        /// Read from the manifest of the table if not given
+        min_length: Option<usize>,
+
+        #[clap(long, short = 't', value_enum)]
+        /// Hash algorithm
+        /// Read from the manifest of the table if not given
+        hash_type: Option<HashType>,
+
+        #[clap(long, short = 's')]
+        /// Charset of the passwords
+        /// Read from the manifest of the table if not given
+        /// Default is A-Z a-z 0-9 ! * for the tables generated before the manifests
+        charset: Option<Charset>,
+
+        #[clap(long)]
+        /// Number of threads
+        /// Default is the number of CPUs, whatever the number of files of the table
+        threads: Option<usize>,
+
+        #[clap(long, short = 'q')]
+        /// Only print the passwords found and the hashs not found
+        quiet: bool,
     },
 }
+/// The passwords of a table: charset and lengths, or mask.
+#[derive(Args)]
+struct KeyspaceArgs {
+    #[clap(long, short = 'l', visible_alias = "max-length", default_value = "7")]
+    /// Password length
+    /// Maximum password length when --min-length is given
+    password_length: usize,
+
+    #[clap(long)]
+    /// Minimum password length
+    /// The table covers every length from min-length to the password length
+    /// Default is the password length
+    min_length: Option<usize>,
+
+    #[clap(long, short = 's', default_value = DEFAULT_CHARSET)]
+    /// Charset of the passwords
+    /// A preset (digits, lower, upper, lower-digits, mixalpha-numeric, base64, ascii)
+    /// or the characters themselves, in the order of their values
+    charset: Charset,
+
+    #[clap(long, conflicts_with_all = ["password_length", "min_length", "charset"])]
+    /// Mask giving the charset of each position, like ?u?l?l?l?l?d?d!
+    /// ?l lowercase, ?u uppercase, ?d digits, ?s special characters, ?a all of them,
+    /// ?h and ?H hexadecimal digits, ?1 to ?4 custom charsets, ?? a literal ?
+    /// Any other character is a literal
+    mask: Option<String>,
+
+    #[clap(long = "custom-charset1", short = '1', requires = "mask")]
+    /// Custom charset ?1 of the mask (placeholders, characters or preset name)
+    custom_charset1: Option<String>,
+
+    #[clap(long = "custom-charset2", short = '2', requires = "mask")]
+    /// Custom charset ?2 of the mask
+    custom_charset2: Option<String>,
+
+    #[clap(long = "custom-charset3", short = '3', requires = "mask")]
+    /// Custom charset ?3 of the mask
+    custom_charset3: Option<String>,
+
+    #[clap(long = "custom-charset4", short = '4', requires = "mask")]
+    /// Custom charset ?4 of the mask
+    custom_charset4: Option<String>,
+}
+
+impl KeyspaceArgs {
+    /// Build the keyspace of the arguments.
+    /// # Returns
+    /// The keyspace, or an error if the mask or the lengths are invalid.
+    fn keyspace(self) -> Result<Keyspace, String> {
+        match self.mask {
+            Some(mask) => {
+                // The custom charsets are numbered from 1, the missing ones stop the list
+                let custom: Vec<String> = [self.custom_charset1, self.custom_charset2, self.custom_charset3, self.custom_charset4].into_iter().map_while(|c| c).collect();
+                Mask::new(&mask, &custom).and_then(Keyspace::from_mask)
+            },
+            None => Keyspace::new(Arc::new(self.charset), self.min_length.unwrap_or(self.password_length), self.password_length),
+        }
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Generation { path, use_mem, chain_length, password_length } => {
-            generation_main(path, use_mem, chain_length, password_length);
+    let result = match cli.command {
+        Commands::Generation { path, use_mem, chain_length, distinguished_bits, tables, format, perfect, hash_type, threads, quiet, chains, coverage, time, max_bytes, keyspace } => {
+            let stop = StopConditions { chains, coverage, time, max_bytes };
+            keyspace.keyspace().and_then(|keyspace| generation_main(path, use_mem, chain_length, distinguished_bits, keyspace, tables, format, perfect, hash_type, threads, quiet, stop))
+        },
+        Commands::Estimate { chains, chain_length, tables, keyspace } => {
+            keyspace.keyspace().and_then(|keyspace| estimate_main(keyspace, chains, chain_length, tables))
+        },
+        Commands::Plan { success, disk, time, hash_type, format, threads, keyspace } => {
+            keyspace.keyspace().and_then(|keyspace| plan_main(keyspace, success, disk, time, hash_type, format, threads))
         },
-        Commands::Search { path, chain_length, hash, hashs_path, password_length} => {
-            search_main(path, chain_length, hash, hashs_path, password_length);
+        Commands::Sort { path, run_chains, perfect } => {
+            sort_main(path, run_chains, perfect)
         },
+        Commands::Search { path, chain_length, hash, hashs_path, password_length, min_length, hash_type, charset, threads, quiet } => {
+            search_main(path, chain_length, hash, hashs_path, password_length, min_length, hash_type, charset, threads, quiet).map(|_| ())
+        },
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::algorithm::HashAlgorithm;
+
+    /// The passwords of a length with the default charset.
+    fn keyspace(length: usize) -> Keyspace {
+        Keyspace::fixed(Arc::new(Charset::preset(DEFAULT_CHARSET).unwrap()), length).unwrap()
+    }
 
     #[test]
     fn test_generation_main() {
-        generation_main(Some(PathBuf::from("./output/")), true, 100, 7);
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-generation-main");
+        let _ = std::fs::remove_dir_all(&dir);
+        let stop = StopConditions { chains: Some(1000), ..Default::default() };
+        generation_main(Some(dir.clone()), true, 100, 0, keyspace(7), 2, TableFormat::Binary, false, HashType::Sha3_256, Some(3), true, stop).unwrap();
+        let manifest = manifest::Manifest::read(&dir).unwrap().unwrap();
+        assert_eq!(manifest.chains, vec![334, 333, 333, 334, 333, 333]);
+
+        // Resumed from the checkpoint with other stop conditions
+        let stop = StopConditions { chains: Some(5000), max_bytes: Some(2 * 32 * 3 + 2 * 12 * 2000), ..Default::default() };
+        generation_main(Some(dir.clone()), true, 100, 0, keyspace(7), 2, TableFormat::Binary, false, HashType::Sha3_256, Some(1), true, stop).unwrap();
+        let manifest = manifest::Manifest::read(&dir).unwrap().unwrap();
+        assert_eq!(manifest.chains.iter().sum::<u64>(), 2 * 2000);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
     fn test_search_main() {
-        search_main(PathBuf::from("./output/"), 100, None, Some(PathBuf::from("./hashs.txt")), 5);
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-search-main");
+        let _ = std::fs::remove_dir_all(&dir);
+        generation_main(Some(dir.clone()), true, 50, 0, keyspace(1), 1, TableFormat::Binary, true, HashType::Md5, None, true, StopConditions::default()).unwrap();
+
+        // The first start point of the perfect table, the parameters being read from the manifest
+        let hash = hash::Hash::new(HashType::Md5.hash(b"0"));
+        let found = search_main(dir.clone(), None, Some(hash.to_string()), None, None, None, None, None, None, true).unwrap();
+        assert_eq!(found, vec![hash]);
+        // The table files of the perfect table were removed
+        assert!(generation_main(Some(dir.clone()), true, 50, 0, keyspace(1), 1, TableFormat::Binary, false, HashType::Md5, None, true, StopConditions::default()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
\ No newline at end of file
@@ -0,0 +1,370 @@
+//! Manifest describing the parameters and the layout of a rainbow table.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::charset::Charset;
+use crate::keyspace::Keyspace;
+use crate::mask::{Mask, CUSTOM_CHARSETS};
+use crate::reduction::{Reduction, REDUCTION_VERSION};
+use crate::algorithm::HashType;
+use crate::table::TableFormat;
+
+/// Name of the manifest file in the table folder.
+pub const MANIFEST_FILE: &str = "manifest.txt";
+
+/// Version of the manifest and table format written by this program.
+/// Version 1 only knew text tables.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The manifest of a rainbow table.
+/// # Fields
+/// * `format_version` - The version of the manifest and table format
+/// * `format` - The format of the table files
+/// * `chain_length` - The length of the chains, the maximum length of the chains of a distinguished-point table
+/// * `distinguished_bits` - The number of low bits at zero of the distinguished points ending the chains, 0 for chains of `chain_length` passwords
+/// * `keyspace` - The passwords of the table (charset, minimum and maximum lengths or mask), used by the reduction function and the keyspace indexes
+/// * `hash` - The name of the hash algorithm
+/// * `reduction_version` - The version of the reduction function
+/// * `tables` - The number of tables, each with its own reduction function
+/// * `files` - The number of files of each table (`test_{0..files}.txt` or `table_{0..files}.bin` for the first table)
+/// * `chains` - The number of chains in each table file, the files of the first table first, or in the sorted table of each table of a perfect table
+/// * `sorted` - If the chains of each table are also in a table sorted by endpoint (`sorted.bin`, `sorted_{table}.bin`)
+/// * `perfect` - If the sorted tables only keep one chain per endpoint, the table files being removed
+/// * `created_at` - The creation date (seconds since the Unix epoch)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub format: TableFormat,
+    pub chain_length: u16,
+    pub distinguished_bits: u8,
+    pub keyspace: Arc<Keyspace>,
+    pub hash: HashType,
+    pub reduction_version: u32,
+    pub tables: u16,
+    pub files: u64,
+    pub chains: Vec<u64>,
+    pub sorted: bool,
+    pub perfect: bool,
+    pub created_at: u64,
+}
+
+impl Manifest {
+    /// Create the manifest of a new table.
+    /// # Arguments
+    /// * `format` - The format of the table files
+    /// * `hash` - The hash algorithm
+    /// * `chain_length` - The length of the chains
+    /// * `distinguished_bits` - The number of low bits at zero of the distinguished points, 0 for chains of fixed length
+    /// * `keyspace` - The passwords of the table
+    /// * `tables` - The number of tables
+    /// * `files` - The number of files of each table
+    /// # Returns
+    /// A new Manifest instance, without any chain.
+    pub fn new(format: TableFormat, hash: HashType, chain_length: u16, distinguished_bits: u8, keyspace: Arc<Keyspace>, tables: u16, files: u64) -> Manifest {
+        Manifest {
+            format_version: FORMAT_VERSION,
+            format,
+            chain_length,
+            distinguished_bits,
+            keyspace,
+            hash,
+            reduction_version: REDUCTION_VERSION,
+            tables,
+            files,
+            chains: vec![0; tables as usize * files as usize],
+            sorted: false,
+            perfect: false,
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        }
+    }
+
+    /// Read the manifest of a table folder.
+    /// # Arguments
+    /// * `path` - The folder containing the rainbow table
+    /// # Returns
+    /// The manifest, None if the folder does not contain one.
+    pub fn read(path: &Path) -> Result<Option<Manifest>, String> {
+        let file = path.join(MANIFEST_FILE);
+        if !file.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&file).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+        Manifest::parse(&contents).map(Some).map_err(|e| format!("Invalid {}: {}", file.display(), e))
+    }
+
+    /// Parse the content of a manifest file.
+    /// # Arguments
+    /// * `contents` - The `key = value` lines of the manifest
+    /// # Returns
+    /// The manifest, or an error if a field is missing or invalid.
+    pub fn parse(contents: &str) -> Result<Manifest, String> {
+        let fields = fields(contents);
+
+        let chains = field::<String>(&fields, "chains")?;
+        let chains = if chains.is_empty() {
+            Vec::new()
+        } else {
+            chains.split(',').map(|c| c.trim().parse::<u64>().map_err(|_| format!("Invalid chain count in manifest: {}", c))).collect::<Result<Vec<u64>, String>>()?
+        };
+
+        let format_version = field(&fields, "format_version")?;
+        // Version 1 manifests only describe unsorted text tables
+        let format = if format_version == 1 { TableFormat::Text } else { field(&fields, "format")? };
+
+        // Version 1 manifests only describe passwords of a single length
+        let keyspace = if format_version == 1 {
+            let charset = Arc::new(Charset::new(fields.get("charset").ok_or("Missing field: charset")?)?);
+            Keyspace::fixed(charset, field(&fields, "password_length")?)?
+        } else if let Some(mask) = fields.get("mask") {
+            let custom = (1..=CUSTOM_CHARSETS).map_while(|i| fields.get(format!("custom_charset{}", i).as_str()).map(|c| c.to_string())).collect::<Vec<String>>();
+            Keyspace::from_mask(Mask::new(mask, &custom)?)?
+        } else {
+            let charset = Arc::new(Charset::new(fields.get("charset").ok_or("Missing field: charset")?)?);
+            Keyspace::new(charset, field(&fields, "min_password_length")?, field(&fields, "password_length")?)?
+        };
+
+        Ok(Manifest {
+            format_version,
+            format,
+            chain_length: field(&fields, "chain_length")?,
+            // Version 1 manifests only describe chains of fixed length
+            distinguished_bits: if format_version == 1 { 0 } else { field(&fields, "distinguished_bits")? },
+            keyspace: Arc::new(keyspace),
+            hash: field(&fields, "hash")?,
+            reduction_version: field(&fields, "reduction_version")?,
+            // Version 1 manifests only describe a single table
+            tables: if format_version == 1 { 1 } else { field(&fields, "tables")? },
+            files: field(&fields, "files")?,
+            chains,
+            sorted: if format_version == 1 { false } else { field(&fields, "sorted")? },
+            perfect: if format_version == 1 { false } else { field(&fields, "perfect")? },
+            created_at: field(&fields, "created_at")?,
+        })
+    }
+
+    /// Write the manifest in the table folder.
+    /// # Arguments
+    /// * `path` - The folder containing the rainbow table
+    /// # Note
+    /// The manifest is written in a temporary file which then replaces the old one,
+    /// so an interrupted write never leaves a truncated manifest.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let tmp = path.join(format!("{}.tmp", MANIFEST_FILE));
+        let mut file = File::create(&tmp)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, path.join(MANIFEST_FILE))
+    }
+
+    /// Check that this program can search the table described by the manifest.
+    /// # Returns
+    /// An error describing the first unsupported parameter.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.format_version == 0 || self.format_version > FORMAT_VERSION {
+            return Err(format!("Unsupported table format version {} (expected {})", self.format_version, FORMAT_VERSION));
+        }
+        // A distinguished point is expected every 2^bits passwords
+        if self.distinguished_bits >= 16 || (1 << self.distinguished_bits) > self.chain_length as u32 {
+            return Err(format!("Chains of at most {} passwords are too short for distinguished points of {} bits", self.chain_length, self.distinguished_bits));
+        }
+        if self.tables == 0 {
+            return Err("The manifest describes no table".to_string());
+        }
+        for table in 0..self.tables {
+            self.reduction(table)?;
+        }
+        // A perfect table only has its sorted tables
+        let counts = if self.perfect { self.tables as u64 } else { self.tables as u64 * self.files };
+        if self.chains.len() as u64 != counts {
+            return Err(format!("The manifest lists {} chain counts for {} tables of {} files", self.chains.len(), self.tables, self.files));
+        }
+        Ok(())
+    }
+
+    /// Get the reduction function of a table.
+    /// # Arguments
+    /// * `table` - The index of the table
+    /// # Returns
+    /// The reduction function, or an error if its version is unknown or cannot build this table.
+    pub fn reduction(&self, table: u16) -> Result<Reduction, String> {
+        Reduction::new(self.keyspace.clone(), self.reduction_version, table)
+    }
+}
+
+/// Write the manifest as `key = value` lines.
+impl Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "# arc-en-ciel rainbow table manifest")?;
+        writeln!(f, "format_version = {}", self.format_version)?;
+        writeln!(f, "format = {}", self.format)?;
+        writeln!(f, "chain_length = {}", self.chain_length)?;
+        writeln!(f, "distinguished_bits = {}", self.distinguished_bits)?;
+        writeln!(f, "min_password_length = {}", self.keyspace.min_length)?;
+        writeln!(f, "password_length = {}", self.keyspace.max_length)?;
+        writeln!(f, "charset = {}", self.keyspace.charset)?;
+        if let Some(mask) = &self.keyspace.mask {
+            writeln!(f, "mask = {}", mask.mask)?;
+            for (i, custom) in mask.custom.iter().enumerate() {
+                writeln!(f, "custom_charset{} = {}", i + 1, custom)?;
+            }
+        }
+        writeln!(f, "hash = {}", self.hash)?;
+        writeln!(f, "reduction_version = {}", self.reduction_version)?;
+        writeln!(f, "tables = {}", self.tables)?;
+        writeln!(f, "files = {}", self.files)?;
+        writeln!(f, "chains = {}", self.chains.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(","))?;
+        writeln!(f, "sorted = {}", self.sorted)?;
+        writeln!(f, "perfect = {}", self.perfect)?;
+        writeln!(f, "created_at = {}", self.created_at)
+    }
+}
+
+/// Check a command line value against the value recorded in the manifest.
+/// # Arguments
+/// * `name` - The name of the parameter
+/// * `cli` - The value given on the command line, if any
+/// * `manifest` - The value recorded in the manifest
+/// # Returns
+/// An error if both values are given and differ.
+pub fn check_override<T: PartialEq + Display>(name: &str, cli: Option<T>, manifest: T) -> Result<(), String> {
+    match cli {
+        Some(value) if value != manifest => Err(format!("The table was generated with {} = {}, not {}", name, manifest, value)),
+        _ => Ok(()),
+    }
+}
+
+/// Split the `key = value` lines of a manifest or a checkpoint.
+/// # Arguments
+/// * `contents` - The content of the file, the lines starting with `#` are comments
+/// # Returns
+/// The values of the keys.
+pub fn fields(contents: &str) -> HashMap<&str, &str> {
+    contents.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        // The values are not trimmed, a charset can start or end with a space
+        .map(|(key, value)| (key.trim(), value.strip_prefix(' ').unwrap_or(value).trim_end_matches('\r')))
+        .collect()
+}
+
+/// Get and parse a field of the manifest or the checkpoint.
+pub fn field<T: FromStr>(fields: &HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    let value = fields.get(key).ok_or(format!("Missing field: {}", key))?.trim();
+    value.parse::<T>().map_err(|_| format!("Invalid value for {}: {}", key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The passwords of the tests with the default charset (`0-9 A-Z a-z ! *`).
+    fn keyspace(min_length: usize, max_length: usize) -> Arc<Keyspace> {
+        Arc::new(Keyspace::new(Arc::new(Charset::preset("base64").unwrap()), min_length, max_length).unwrap())
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let mut manifest = Manifest::new(TableFormat::Binary, HashType::Sha3_256, 100, 0, keyspace(7, 7), 1, 4);
+        manifest.chains = vec![10, 20, 30, 40];
+        let parsed = Manifest::parse(&manifest.to_string()).unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(parsed.validate().is_ok());
+    }
+
+    #[test]
+    fn test_manifest_lengths() {
+        let manifest = Manifest::new(TableFormat::Text, HashType::Md5, 100, 0, keyspace(1, 8), 1, 1);
+        let parsed = Manifest::parse(&manifest.to_string()).unwrap();
+        assert_eq!(parsed.keyspace.min_length, 1);
+        assert_eq!(parsed.keyspace.max_length, 8);
+        assert!(Manifest::parse(&manifest.to_string().replace("min_password_length = 1", "min_password_length = 9")).is_err());
+    }
+
+    #[test]
+    fn test_manifest_mask() {
+        let mask = Mask::new("?1?1 ?d=", &[" ?u".to_string(), "digits".to_string()]).unwrap();
+        let manifest = Manifest::new(TableFormat::Binary, HashType::Md5, 100, 0, Arc::new(Keyspace::from_mask(mask).unwrap()), 1, 1);
+        let parsed = Manifest::parse(&manifest.to_string()).unwrap();
+        assert_eq!(parsed, manifest);
+        assert_eq!(parsed.keyspace.size(), 27 * 27 * 10);
+    }
+
+    #[test]
+    fn test_manifest_invalid() {
+        let mut manifest = Manifest::new(TableFormat::Binary, HashType::Sha3_256, 100, 0, keyspace(7, 7), 1, 4);
+        manifest.reduction_version += 1;
+        assert!(manifest.validate().is_err());
+        // Tables of the first reduction function are still supported
+        manifest.reduction_version = 1;
+        assert!(manifest.validate().is_ok());
+        assert!(Manifest::parse("chain_length = 100").is_err());
+    }
+
+    #[test]
+    fn test_manifest_tables() {
+        let mut manifest = Manifest::new(TableFormat::Binary, HashType::Sha3_256, 100, 0, keyspace(7, 7), 3, 2);
+        assert_eq!(manifest.chains.len(), 6);
+        assert_eq!(Manifest::parse(&manifest.to_string()).unwrap(), manifest);
+        assert!(manifest.validate().is_ok());
+        assert!(Manifest::parse(&manifest.to_string().replace("tables = 3\n", "")).is_err());
+        // A perfect table has a sorted table per table
+        manifest.perfect = true;
+        assert!(manifest.validate().is_err());
+        manifest.chains = vec![10, 20, 30];
+        assert!(manifest.validate().is_ok());
+        // The first reduction function has a single family
+        manifest.reduction_version = 1;
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn test_manifest_distinguished() {
+        let mut manifest = Manifest::new(TableFormat::Binary, HashType::Md5, 100, 6, keyspace(7, 7), 1, 1);
+        assert_eq!(Manifest::parse(&manifest.to_string()).unwrap(), manifest);
+        assert!(manifest.validate().is_ok());
+        assert!(Manifest::parse(&manifest.to_string().replace("distinguished_bits = 6\n", "")).is_err());
+        // 2^7 > 100: most chains would not reach a distinguished point
+        manifest.distinguished_bits = 7;
+        assert!(manifest.validate().is_err());
+        manifest.distinguished_bits = 40;
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn test_check_override() {
+        assert!(check_override("chain_length", None, 100).is_ok());
+        assert!(check_override("chain_length", Some(100), 100).is_ok());
+        assert!(check_override("chain_length", Some(50), 100).is_err());
+    }
+
+    #[test]
+    fn test_manifest_charset_with_spaces() {
+        let charset = Arc::new(Charset::new(" a=b#").unwrap());
+        let manifest = Manifest::new(TableFormat::Binary, HashType::Md5, 100, 0, Arc::new(Keyspace::fixed(charset.clone(), 7).unwrap()), 1, 1);
+        assert_eq!(Manifest::parse(&manifest.to_string()).unwrap().keyspace.charset, charset);
+    }
+
+    #[test]
+    fn test_manifest_version_1() {
+        // The manifests of version 1 describe the alphabet of the first reduction function
+        let legacy = Arc::new(Keyspace::fixed(Charset::legacy(), 7).unwrap());
+        let mut manifest = Manifest::new(TableFormat::Binary, HashType::Sha3_256, 100, 0, legacy, 1, 1);
+        manifest.format_version = 1;
+        manifest.reduction_version = 1;
+        let contents = manifest.to_string().replace("format = binary\n", "").replace("min_password_length = 7\n", "").replace("distinguished_bits = 0\n", "").replace("tables = 1\n", "").replace("sorted = false\n", "");
+        let parsed = Manifest::parse(&contents).unwrap();
+        assert_eq!(parsed.format, TableFormat::Text);
+        assert_eq!(parsed.distinguished_bits, 0);
+        assert_eq!(parsed.keyspace, manifest.keyspace);
+        assert_eq!(parsed.keyspace.charset.to_string(), crate::charset::LEGACY_CHARSET);
+        assert_eq!(parsed.reduction(0).unwrap().version, 1);
+        assert!(parsed.validate().is_ok());
+    }
+}
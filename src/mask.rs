@@ -0,0 +1,139 @@
+//! Hashcat-style masks: one charset per position of the password.
+//!
+//! A mask is made of placeholders and literal characters:
+//! * `?l` lowercase letters, `?u` uppercase letters, `?d` digits, `?s` special characters
+//! * `?a` all of them (`?l?u?d?s`), `?h` and `?H` lowercase and uppercase hexadecimal digits
+//! * `?1` to `?4` the custom charsets, `??` a literal `?`
+//! * any other printable ASCII character is a literal
+//!
+//! For example, `?u?l?l?l?l?d?d!` describes the passwords like `Hello42!`.
+
+use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::charset::Charset;
+
+/// Maximum number of custom charsets (`?1` to `?4`).
+pub const CUSTOM_CHARSETS: usize = 4;
+
+/// The special characters of `?s`.
+const SPECIAL: &str = " !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// A mask describing the charset of each position of the passwords.
+/// # Fields
+/// * `mask` - The mask as given by the user
+/// * `custom` - The definitions of the custom charsets `?1` to `?4`
+/// * `positions` - The charset of each position
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    pub mask: String,
+    pub custom: Vec<String>,
+    positions: Vec<Arc<Charset>>,
+}
+
+impl Mask {
+    /// Parse a mask.
+    /// # Arguments
+    /// * `mask` - The placeholders and literal characters
+    /// * `custom` - The definitions of the custom charsets, with placeholders and literal characters or a charset preset name
+    /// # Returns
+    /// The mask, or an error if a placeholder is unknown or a charset is invalid.
+    pub fn new(mask: &str, custom: &[String]) -> Result<Mask, String> {
+        if custom.len() > CUSTOM_CHARSETS {
+            return Err(format!("A mask has at most {} custom charsets", CUSTOM_CHARSETS));
+        }
+        let charsets = custom.iter().map(|definition| match Charset::preset(definition) {
+            Some(charset) => Ok(Arc::new(charset)),
+            None => Ok(Arc::new(Charset::new(&expand(definition, &[])?.concat())?)),
+        }).collect::<Result<Vec<Arc<Charset>>, String>>()?;
+
+        let positions = expand(mask, &charsets)?.iter().map(|chars| Charset::new(chars).map(Arc::new)).collect::<Result<Vec<Arc<Charset>>, String>>()?;
+        if positions.is_empty() {
+            return Err("The mask is empty".to_string());
+        }
+        Ok(Mask { mask: mask.to_string(), custom: custom.to_vec(), positions })
+    }
+
+    /// Get the charset of each position.
+    pub fn positions(&self) -> &[Arc<Charset>] {
+        &self.positions
+    }
+}
+
+/// Display the mask and its custom charsets as in the command line.
+impl Display for Mask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.mask)?;
+        for (i, definition) in self.custom.iter().enumerate() {
+            write!(f, " -{} {}", i + 1, definition)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replace the placeholders of a mask by their characters.
+/// # Arguments
+/// * `mask` - The placeholders and literal characters
+/// * `custom` - The custom charsets `?1` to `?4`, empty in the definition of a custom charset
+/// # Returns
+/// The characters of each position.
+fn expand(mask: &str, custom: &[Arc<Charset>]) -> Result<Vec<String>, String> {
+    let mut positions = Vec::new();
+    let mut chars = mask.chars();
+    while let Some(c) = chars.next() {
+        if c != '?' {
+            positions.push(c.to_string());
+            continue;
+        }
+        let placeholder = chars.next().ok_or(format!("The mask {} ends with a lone ?", mask))?;
+        positions.push(match placeholder {
+            'l' => Charset::preset("lower").unwrap().to_string(),
+            'u' => Charset::preset("upper").unwrap().to_string(),
+            'd' => Charset::preset("digits").unwrap().to_string(),
+            'h' => "0123456789abcdef".to_string(),
+            'H' => "0123456789ABCDEF".to_string(),
+            's' => SPECIAL.to_string(),
+            'a' => format!("{}{}{}{}", Charset::preset("lower").unwrap(), Charset::preset("upper").unwrap(), Charset::preset("digits").unwrap(), SPECIAL),
+            '?' => "?".to_string(),
+            '1'..='4' => {
+                let i = placeholder.to_digit(10).unwrap() as usize - 1;
+                custom.get(i).ok_or(format!("The custom charset ?{} is not defined", i + 1))?.to_string()
+            },
+            _ => return Err(format!("Unknown placeholder ?{} in the mask {}", placeholder, mask)),
+        });
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders() {
+        let mask = Mask::new("?u?l?l?l?l?d?d!", &[]).unwrap();
+        let sizes: Vec<u64> = mask.positions().iter().map(|charset| charset.len()).collect();
+        assert_eq!(sizes, vec![26, 26, 26, 26, 26, 10, 10, 1]);
+        assert_eq!(Mask::new("?a?s??", &[]).unwrap().positions().iter().map(|charset| charset.len()).collect::<Vec<u64>>(), vec![95, 33, 1]);
+    }
+
+    #[test]
+    fn test_custom() {
+        let custom = vec!["?l?d".to_string(), "digits".to_string(), "aeiou".to_string()];
+        let mask = Mask::new("?1?2?3x", &custom).unwrap();
+        assert_eq!(mask.positions()[0].len(), 36);
+        assert_eq!(mask.positions()[1].len(), 10);
+        assert_eq!(mask.positions()[2].to_string(), "aeiou");
+        assert_eq!(mask.to_string(), "?1?2?3x -1 ?l?d -2 digits -3 aeiou");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Mask::new("", &[]).is_err());
+        assert!(Mask::new("?d?", &[]).is_err());
+        assert!(Mask::new("?x", &[]).is_err());
+        assert!(Mask::new("?1", &[]).is_err());
+        assert!(Mask::new("?1", &["?d?d".to_string()]).is_err());
+        assert!(Mask::new("?d\n", &[]).is_err());
+    }
+}
@@ -1,187 +1,213 @@
 //! Implements traits and methods for a password type.
 
-use rayon::prelude::*;
-use std::ops::{Add, Sub};
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::keyspace::Keyspace;
 
 /// The password type.
 /// # Fields
-/// * `password` - The password
-/// * `length` - The length of the password
+/// * `index` - The keyspace index of the password
+/// * `keyspace` - The passwords of the table
 /// # Note
-/// The password is stored as a string.
-/// The length is stored as an usize.
+/// The password is stored as its keyspace index: the arithmetic and the comparisons are done on the index,
+/// the string is only rendered when the password is displayed.
+/// The passwords of the next length follow the longest password of a length.
 #[derive(Clone, Debug)]
 pub struct Password {
-    pub password: String,
-    length: usize,
+    index: u128,
+    keyspace: Arc<Keyspace>,
 }
 
-/// Add an offset to the password.
-impl Add<u64> for Password {
-    type Output = Password;
-
-    fn add(self, offset: u64) -> Password {
-        let mut password = self.to_b64();
-
-        let mut carry = offset;
-        for i in (0..self.length).rev() {
-            let sum = password[i] + carry;
-            password[i] = sum % 64;
-            carry = sum / 64;
-            if carry == 0 {
-                break;
-            }
-        }
-
-        let new = Password::from_b64(password);
-        if new < self {
-            Password::new("?".to_string())
-        } else {
-            new
-        }
+/// Compare two passwords.
+impl PartialEq for Password {
+    fn eq(&self, other: &Password) -> bool {
+        self.index == other.index
     }
 }
 
-/// Subtract an offset to the password.
-impl Sub<u64> for Password {
-    type Output = Password;
-
-    fn sub(self, offset: u64) -> Password {
-        let mut password = self.to_b64();
-        let mut offset = offset as i128;
-        for i in (0..self.length).rev() {
-            let sum: i128 = password[i] as i128 - offset;
-            if sum < 0 {
-                password[i] = ((64 - (sum.abs() & 63)) & 63) as u64;
-                offset = (sum.abs() - 1) / 64 + 1;
-            } else {
-                password[i] = sum as u64;
-                break;
-            }
-        }
+impl Eq for Password {}
 
-        Password::from_b64(password)
+/// Compare two passwords.
+impl PartialOrd for Password {
+    fn partial_cmp(&self, other: &Password) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-/// Compare two passwords.
-impl PartialEq for Password {
-    fn eq(&self, other: &Password) -> bool {
-        self.password == other.password
+/// Compare two passwords by keyspace index.
+impl Ord for Password {
+    fn cmp(&self, other: &Password) -> Ordering {
+        self.index.cmp(&other.index)
     }
 }
 
-/// Compare two passwords.
-impl PartialOrd for Password {
-    fn partial_cmp(&self, other: &Password) -> Option<std::cmp::Ordering> {
-        let password = self.to_b64().iter().fold(0, |acc, x| acc * 64 + x);
-        password.partial_cmp(&other.to_b64().iter().fold(0, |acc, x| acc * 64 + x))
+/// Render the password.
+impl Display for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.keyspace.password(self.index))
     }
 }
 
 impl Password {
-    fn to_b64(&self) -> Vec<u64> {
-        self.password.clone().into_bytes().into_par_iter().map(|c| 
-            match c {
-                48..=57 => c - 48,
-                65..=90 => c - 55,
-                97..=122 => c - 61,
-                33 => 62,
-                42 => 63,
-                _ => c,
-            } as u64
-        ).collect::<Vec<u64>>()
-    }
-
-    pub fn from_b64(b64: Vec<u64>) -> Password {
-        let password = b64.into_par_iter().map(|c| 
-            match c {
-                0..=9 => c + 48,
-                10..=35 => c + 55,
-                36..=61 => c + 61,
-                62 => 33,
-                63 => 42,
-                _ => c,
-            } as u8
-        ).map(|c| c as char).collect::<String>();
-        Password::new(password)
-    }
-
-    pub fn new (password: String) -> Password {
-        let length = password.len();
-        Password { password, length }
+    /// Get the keyspace index of the password.
+    pub fn to_index(&self) -> u128 {
+        self.index
+    }
+
+    /// Create the password of a keyspace index.
+    /// # Arguments
+    /// * `index` - The keyspace index
+    /// * `keyspace` - The passwords of the table
+    /// # Returns
+    /// The password, None if the index is not lower than the size of the keyspace.
+    pub fn from_index(index: u128, keyspace: &Arc<Keyspace>) -> Option<Password> {
+        if index < keyspace.size() {
+            Some(Password { index, keyspace: keyspace.clone() })
+        } else {
+            None
+        }
+    }
+
+    /// Create a password of a keyspace.
+    /// # Returns
+    /// The password, None if it is not in the keyspace.
+    #[cfg(test)]
+    pub fn with_keyspace(password: &str, keyspace: &Arc<Keyspace>) -> Option<Password> {
+        keyspace.index(password).map(|index| Password { index, keyspace: keyspace.clone() })
+    }
+
+    /// Add an offset to the password.
+    /// # Returns
+    /// The password, None if it is after the last password of the keyspace.
+    pub fn checked_add(&self, offset: u128) -> Option<Password> {
+        self.index.checked_add(offset).and_then(|index| Password::from_index(index, &self.keyspace))
+    }
+
+    /// Subtract an offset to the password.
+    /// # Returns
+    /// The password, None if it is before the first password of the keyspace.
+    pub fn checked_sub(&self, offset: u128) -> Option<Password> {
+        self.index.checked_sub(offset).and_then(|index| Password::from_index(index, &self.keyspace))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::charset::Charset;
+
+    /// The passwords of a length with the default charset (`0-9 A-Z a-z ! *`).
+    fn keyspace(length: usize) -> Arc<Keyspace> {
+        Arc::new(Keyspace::fixed(Arc::new(Charset::preset("base64").unwrap()), length).unwrap())
+    }
+
+    /// Create a password with the default charset, in the keyspace of its length.
+    fn base64(password: &str) -> Option<Password> {
+        Password::with_keyspace(password, &keyspace(password.len()))
+    }
+
+    /// Add an offset to a password of the default charset.
+    fn add(password: &str, offset: u128) -> Option<String> {
+        base64(password).unwrap().checked_add(offset).map(|p| p.to_string())
+    }
+
+    /// Subtract an offset to a password of the default charset.
+    fn sub(password: &str, offset: u128) -> Option<String> {
+        base64(password).unwrap().checked_sub(offset).map(|p| p.to_string())
+    }
 
     #[test]
     fn test_add() {
-        let password = Password::new("8000000".to_string());
-        let password = password + 1;
-        assert_eq!(password.password, "8000001");
+        assert_eq!(add("8000000", 1).unwrap(), "8000001");
     }
 
     #[test]
     fn test_add_with_carry() {
-        let password = Password::new("8000009".to_string());
-        let password = password + 2;
-        println!("{:?}", password.password);
-        assert_eq!(password.password, "800000B");
+        assert_eq!(add("8000009", 2).unwrap(), "800000B");
     }
 
     #[test]
     fn test_add_with_overflow() {
-        let password = Password::new("******Z".to_string());
-        let password = password + 100;
-        assert_eq!(password.password, "?");
+        assert_eq!(add("******Z", 100), None);
+        assert_eq!(add("******Z", u128::MAX), None);
     }
 
     #[test]
     fn test_sub() {
-        let password = Password::new("8000001".to_string());
-        let password = password - 1;
-        assert_eq!(password.password, "8000000");
+        assert_eq!(sub("8000001", 1).unwrap(), "8000000");
     }
 
     #[test]
     fn test_sub_with_carry() {
-        let password = Password::new("80000a0".to_string());
-        let password = password - 64;
-        assert_eq!(password.password, "80000Z0");
+        assert_eq!(sub("80000a0", 64).unwrap(), "80000Z0");
+    }
+
+    #[test]
+    fn test_sub_with_overflow() {
+        assert_eq!(sub("0000001", 2), None);
     }
 
     #[test]
     fn test_sub_complex() {
         for i in 0..1000 {
-            let password = Password::new("800000!".to_string());
-            let password = password + i;
-            println!("{:?}", password.password);
-            let password = password - i;
-            assert_eq!(password.password, "800000!");
+            let password = base64("800000!").unwrap();
+            let password = password.checked_add(i).unwrap().checked_sub(i).unwrap();
+            assert_eq!(password.to_string(), "800000!");
         }
     }
 
+    #[test]
+    fn test_index() {
+        let password = base64("80000a0").unwrap();
+        assert_eq!(Password::from_index(password.to_index(), &keyspace(7)).unwrap(), password);
+        assert_eq!(base64("00010").unwrap().to_index(), 64);
+        assert_eq!(Password::from_index(63, &keyspace(2)).unwrap().to_string(), "0*");
+        assert!(Password::from_index(64 * 64, &keyspace(2)).is_none());
+        assert!(base64("abc?").is_none());
+    }
+
+    #[test]
+    fn test_long_passwords() {
+        // 64^20 passwords do not fit in 64 bits
+        let password = base64("********************").unwrap();
+        assert_eq!(password.to_index(), 64u128.pow(20) - 1);
+        assert_eq!(password.checked_sub(64).unwrap().to_string(), "******************!*");
+        assert!(password.checked_add(1).is_none());
+    }
+
+    #[test]
+    fn test_digits_charset() {
+        let digits = Arc::new(Keyspace::fixed(Arc::new(Charset::preset("digits").unwrap()), 4).unwrap());
+        let password = Password::with_keyspace("0999", &digits).unwrap().checked_add(1).unwrap();
+        assert_eq!(password.to_string(), "1000");
+        assert_eq!(password.to_index(), 1000);
+        assert_eq!(password.checked_add(8999).unwrap().to_string(), "9999");
+        assert!(Password::with_keyspace("9998", &digits).unwrap().checked_add(2).is_none());
+    }
+
+    #[test]
+    fn test_add_next_length() {
+        let keyspace = Arc::new(Keyspace::new(Arc::new(Charset::preset("digits").unwrap()), 1, 3).unwrap());
+        let password = Password::with_keyspace("99", &keyspace).unwrap().checked_add(1).unwrap();
+        assert_eq!(password.to_string(), "000");
+        assert_eq!(password.checked_sub(2).unwrap().to_string(), "98");
+        assert!(Password::with_keyspace("9", &keyspace) < Password::with_keyspace("00", &keyspace));
+    }
+
     #[test]
     fn test_eq() {
-        let password = Password::new("8000000".to_string());
-        let password2 = Password::new("8000000".to_string());
-        assert_eq!(password == password2, true);
+        assert!(base64("8000000") == base64("8000000"));
     }
 
     #[test]
     fn test_gt() {
-        let password = Password::new("8020000".to_string());
-        let password2 = Password::new("800!000".to_string());
-        assert_eq!(password > password2, true);
+        assert!(base64("8020000") > base64("800!000"));
     }
 
     #[test]
     fn test_lt() {
-        let password = Password::new("802000z".to_string());
-        let password2 = Password::new("802000!".to_string());
-        assert_eq!(password < password2, true);
+        assert!(base64("802000z") < base64("802000!"));
     }
-}
\ No newline at end of file
+}
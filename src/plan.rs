@@ -0,0 +1,289 @@
+//! Choose the parameters of rainbow tables (chain length, number of chains and number of tables)
+//! from a target success probability, a disk budget and a maximum search time per hash.
+//!
+//! The hash speed of the local machine is measured to convert the search time in a number of hashes,
+//! then the estimates of Oechslin's formulas are compared for every chain length and number of tables.
+
+use std::time::{Duration, Instant};
+
+use crate::algorithm::{HashAlgorithm, HashType};
+use crate::estimate::{bytes, Estimate};
+use crate::generation::{start_step, CHAIN_LENGTH_MAX, CHAIN_LENGTH_MIN};
+use crate::keyspace::Keyspace;
+use crate::reduction::{Reduction, REDUCTION_VERSION};
+use crate::table::TableFormat;
+
+/// Maximum number of tables proposed.
+const TABLES_MAX: u16 = 8;
+
+/// Duration of the measure of the hash speed.
+const MEASURE_TIME: Duration = Duration::from_millis(300);
+
+/// Plan rainbow tables (main function).
+/// # Arguments
+/// * `keyspace` - The passwords of the tables
+/// * `success` - The target success probability, between 0 and 1
+/// * `disk` - The disk budget in bytes
+/// * `time` - The maximum search time per hash in seconds
+/// * `hash_type` - The hash algorithm of the tables
+/// * `format` - The format of the table files
+/// * `threads` - The number of threads of the generation, the number of CPUs if not given
+/// # Returns
+/// An error if the parameters are invalid or if no tables reach the target within the budgets.
+/// # Note
+/// Among the parameters reaching the target, the ones with the lowest generation cost are proposed,
+/// with the generation command building these tables.
+pub fn plan_main(keyspace: Keyspace, success: f64, disk: u64, time: f64, hash_type: HashType, format: TableFormat, threads: Option<usize>) -> Result<(), String> {
+    if !(success > 0.0 && success < 1.0) {
+        return Err(format!("The success probability must be between 0 and 1, not {}", success));
+    }
+    if time <= 0.0 {
+        return Err(format!("The search time must be positive, not {}", time));
+    }
+
+    let threads = threads.unwrap_or_else(num_cpus::get);
+    if threads == 0 {
+        return Err("At least one thread is needed".to_string());
+    }
+
+    let speed = measure_speed(&hash_type, &keyspace);
+    println!("Measured speed: {:.0} hashes per second per thread", speed);
+
+    let estimate = best_plan(&keyspace, success, disk, time * speed, format, threads as u64)
+        .ok_or(format!("No tables reach {:.2}% with {} of disk and {} s per hash: increase the disk budget or the search time", 100.0 * success, bytes(disk as f64), time))?;
+    println!("Proposed parameters: -c {} --tables {} --threads {}, {} chains per table", estimate.chain_length, estimate.tables, threads, estimate.chains);
+    println!("Generation command: {}", generation_command(&keyspace, &estimate, hash_type, format, threads));
+    print!("{}", estimate);
+    println!("Search time: {:.3} s per hash", estimate.search_cost() / speed);
+    println!("Generation time: {:.0} s with {} thread{}", estimate.generation_cost() / speed / threads as f64, threads, if threads > 1 { "s" } else { "" });
+    Ok(())
+}
+
+/// Find the cheapest tables reaching a success probability.
+/// # Arguments
+/// * `keyspace` - The passwords of the tables
+/// * `success` - The target success probability
+/// * `disk` - The disk budget in bytes
+/// * `search_hashes` - The maximum number of hashes computed to search a hash
+/// * `format` - The format of the table files
+/// * `files` - The number of files of each table (the threads of the generation)
+/// # Returns
+/// The estimate of the tables with the lowest generation cost, None if no tables fit in the budgets.
+/// # Note
+/// For each chain length and number of tables, the number of chains is the smallest one reaching the target.
+pub fn best_plan(keyspace: &Keyspace, success: f64, disk: u64, search_hashes: f64, format: TableFormat, files: u64) -> Option<Estimate> {
+    let mut best: Option<Estimate> = None;
+    for tables in 1..=TABLES_MAX {
+        // Success probability needed from each table
+        let table_success = 1.0 - (1.0 - success).powf(1.0 / tables as f64);
+        for chain_length in chain_lengths() {
+            let Some(estimate) = smallest_table(keyspace, table_success, chain_length, tables, files) else {
+                continue;
+            };
+            let size = estimate.sizes.iter().find(|(f, _, _)| *f == format).map(|(_, size, _)| *size).unwrap_or(f64::INFINITY);
+            if size > disk as f64 || estimate.search_cost() > search_hashes {
+                continue;
+            }
+            if best.as_ref().is_none_or(|best| estimate.generation_cost() < best.generation_cost()) {
+                best = Some(estimate);
+            }
+        }
+    }
+    best
+}
+
+/// Find the smallest number of chains of tables reaching a success probability per table.
+/// # Returns
+/// The estimate of the tables, None if even every start point of the generation does not reach it.
+fn smallest_table(keyspace: &Keyspace, table_success: f64, chain_length: u16, tables: u16, files: u64) -> Option<Estimate> {
+    let reaches = |chains| Estimate::new(keyspace, chains, chain_length, tables).ok().filter(|estimate| estimate.table_success >= table_success);
+    let max = max_chains(keyspace, chain_length, files).min(u64::MAX as u128) as u64;
+    reaches(max)?;
+    // Binary search of the number of chains, the success probability grows with it
+    let (mut low, mut high) = (1, max);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if reaches(middle).is_some() {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    reaches(low)
+}
+
+/// Get the number of chains of a table generated from all its start points.
+/// # Arguments
+/// * `keyspace` - The passwords of the table
+/// * `chain_length` - The length of the chains
+/// * `files` - The number of files of the table
+/// # Returns
+/// `files · size / start_step`, at most one chain per password.
+/// # Note
+/// Each file of the generation starts a chain every `start_step` passwords of the keyspace.
+fn max_chains(keyspace: &Keyspace, chain_length: u16, files: u64) -> u128 {
+    (keyspace.size() / start_step(chain_length)).saturating_mul(files as u128).min(keyspace.size())
+}
+
+/// Get the generation command of planned tables.
+/// # Arguments
+/// * `keyspace` - The passwords of the tables
+/// * `estimate` - The planned tables
+/// * `hash_type` - The hash algorithm of the tables
+/// * `format` - The format of the table files
+/// * `threads` - The number of threads of the generation, giving the number of files of each table
+/// # Returns
+/// The command line, with the charsets and the mask quoted for the shell.
+/// # Note
+/// The `--chains` stop condition is given when the tables have fewer chains than the start points of the generation.
+fn generation_command(keyspace: &Keyspace, estimate: &Estimate, hash_type: HashType, format: TableFormat, threads: usize) -> String {
+    let mut command = format!("cargo run -- generation -c {} --tables {} -t {} -f {} --threads {}", estimate.chain_length, estimate.tables, hash_type, format, threads);
+    if (estimate.chains as u128) < max_chains(keyspace, estimate.chain_length, threads as u64) {
+        command.push_str(&format!(" --chains {}", estimate.chains));
+    }
+    match &keyspace.mask {
+        Some(mask) => {
+            command.push_str(&format!(" --mask {}", quote(&mask.mask)));
+            for (i, custom) in mask.custom.iter().enumerate() {
+                command.push_str(&format!(" -{} {}", i + 1, quote(custom)));
+            }
+        },
+        None => {
+            let charset = keyspace.charset.preset_name().map(str::to_string).unwrap_or_else(|| keyspace.charset.to_string());
+            command.push_str(&format!(" -l {} --min-length {} -s {}", keyspace.max_length, keyspace.min_length, quote(&charset)));
+        },
+    }
+    command
+}
+
+/// Quote an argument for a POSIX shell.
+fn quote(argument: &str) -> String {
+    format!("'{}'", argument.replace('\'', "'\\''"))
+}
+
+/// Get the chain lengths compared by the planner, from `CHAIN_LENGTH_MIN` to `CHAIN_LENGTH_MAX` with a ratio of about 1.1.
+fn chain_lengths() -> Vec<u16> {
+    let mut lengths = vec![CHAIN_LENGTH_MIN];
+    while let Some(&last) = lengths.last().filter(|&&last| last < CHAIN_LENGTH_MAX) {
+        lengths.push((last + 1).max(last + last / 10).min(CHAIN_LENGTH_MAX));
+    }
+    lengths
+}
+
+/// Measure the speed of a step of a chain (password, hash and reduction) on a thread.
+/// # Returns
+/// The number of steps per second.
+fn measure_speed(hasher: &HashType, keyspace: &Keyspace) -> f64 {
+    let reduction = Reduction::new(std::sync::Arc::new(keyspace.clone()), REDUCTION_VERSION, 0).unwrap();
+    let start = Instant::now();
+    let mut index = 0;
+    let mut steps = 0u64;
+    // The buffers are reused as in the generation
+    let mut password = Vec::with_capacity(keyspace.max_length);
+    let mut digest = vec![0; hasher.digest_size()];
+    while start.elapsed() < MEASURE_TIME {
+        for _ in 0..1000 {
+            keyspace.password_into(index, &mut password);
+            hasher.hash_into(&password, &mut digest);
+            index = reduction.reduce_index(&digest, 0);
+        }
+        steps += 1000;
+    }
+    steps as f64 / start.elapsed().as_secs_f64()
+}
+
+/// Parse a size in bytes, with an optional unit (K, M, G, T for powers of 1024, KB, MB, GB, TB for powers of 1000).
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.trim().parse().map_err(|_| format!("Invalid size: {}", size))?;
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KIB" => 1024.0,
+        "M" | "MIB" => 1024.0_f64.powi(2),
+        "G" | "GIB" => 1024.0_f64.powi(3),
+        "T" | "TIB" => 1024.0_f64.powi(4),
+        "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return Err(format!("Unknown size unit: {}", unit)),
+    };
+    Ok((number * multiplier) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::charset::Charset;
+    use crate::mask::Mask;
+
+    /// The passwords of a length with a preset charset.
+    fn keyspace(charset: &str, length: usize) -> Keyspace {
+        Keyspace::fixed(Arc::new(Charset::preset(charset).unwrap()), length).unwrap()
+    }
+
+    #[test]
+    fn test_best_plan() {
+        let keyspace = keyspace("lower", 5);
+        let estimate = best_plan(&keyspace, 0.95, 1 << 20, 1e6, TableFormat::Binary, 4).unwrap();
+        assert!(estimate.success >= 0.95);
+        assert!(estimate.sizes[1].1 <= (1 << 20) as f64);
+        assert!(estimate.search_cost() <= 1e6);
+        // A smaller search budget needs shorter chains, so more of them
+        let fast = best_plan(&keyspace, 0.95, 1 << 24, 1e4, TableFormat::Binary, 4).unwrap();
+        assert!(fast.chain_length < estimate.chain_length);
+        assert!(fast.search_cost() <= 1e4);
+        // Not enough disk for the fast search
+        assert!(best_plan(&keyspace, 0.95, 1 << 10, 1e4, TableFormat::Binary, 4).is_none());
+    }
+
+    #[test]
+    fn test_max_chains() {
+        // A file starts a chain every 70 passwords of the 10000 passwords
+        let keyspace = keyspace("digits", 4);
+        assert_eq!(max_chains(&keyspace, 100, 4), 4 * 142);
+        assert_eq!(max_chains(&keyspace, 1, 4), 10000);
+        // The planned tables can be generated with these files
+        let estimate = best_plan(&keyspace, 0.5, 1 << 20, 1e6, TableFormat::Binary, 4).unwrap();
+        assert!(estimate.chains as u128 <= max_chains(&keyspace, estimate.chain_length, 4));
+    }
+
+    #[test]
+    fn test_generation_command() {
+        let estimate = Estimate::new(&keyspace("lower", 5), 1000, 100, 2).unwrap();
+        assert_eq!(generation_command(&keyspace("lower", 5), &estimate, HashType::Md5, TableFormat::Binary, 4),
+            "cargo run -- generation -c 100 --tables 2 -t md5 -f binary --threads 4 --chains 1000 -l 5 --min-length 5 -s 'lower'");
+        let keyspace = Keyspace::new(Arc::new(Charset::new("a'b").unwrap()), 1, 3).unwrap();
+        assert!(generation_command(&keyspace, &estimate, HashType::Sha1, TableFormat::Text, 1).ends_with("-l 3 --min-length 1 -s 'a'\\''b'"));
+        let mask = Mask::new("?1?d", &["?l?u".to_string()]).unwrap();
+        assert!(generation_command(&Keyspace::from_mask(mask).unwrap(), &estimate, HashType::Md5, TableFormat::Binary, 1).ends_with("--mask '?1?d' -1 '?l?u'"));
+    }
+
+    #[test]
+    fn test_plan_main() {
+        assert!(plan_main(keyspace("digits", 4), 0.9, 1 << 20, 1.0, HashType::Md5, TableFormat::Binary, Some(2)).is_ok());
+        assert!(plan_main(keyspace("digits", 4), 0.9, 1 << 20, 1.0, HashType::Md5, TableFormat::Binary, Some(0)).is_err());
+        assert!(plan_main(keyspace("digits", 4), 1.5, 1 << 20, 1.0, HashType::Md5, TableFormat::Binary, None).is_err());
+    }
+
+    #[test]
+    fn test_chain_lengths() {
+        let lengths = chain_lengths();
+        assert_eq!(lengths[..3], [1, 2, 3]);
+        assert_eq!(*lengths.last().unwrap(), CHAIN_LENGTH_MAX);
+        assert!(lengths.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("1.5K"), Ok(1536));
+        assert_eq!(parse_size("10 GiB"), Ok(10 << 30));
+        assert_eq!(parse_size("2GB"), Ok(2_000_000_000));
+        assert!(parse_size("2 parsecs").is_err());
+        assert!(parse_size("G").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! Live progress of the generation and the search.
+//!
+//! A background thread prints a progress line on the standard error at regular intervals,
+//! computed from counters updated by the worker threads. The line is rewritten in place on a terminal,
+//! and printed once per interval otherwise (logs). The quiet mode prints nothing.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::estimate::bytes;
+
+/// Interval between two progress lines.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reporter printing a progress line until it is finished.
+/// # Fields
+/// * `stop` - The flag stopping the reporter thread
+/// * `thread` - The reporter thread, None in quiet mode
+pub struct Progress {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Progress {
+    /// Start printing the progress.
+    /// # Arguments
+    /// * `quiet` - Print nothing
+    /// * `line` - The function giving the progress line from the time elapsed since the start
+    /// # Note
+    /// The last line is printed again when the reporter is finished, so a short run still shows its summary.
+    pub fn start<F: Fn(Duration) -> String + Send + 'static>(quiet: bool, line: F) -> Progress {
+        let stop = Arc::new(AtomicBool::new(false));
+        if quiet {
+            return Progress { stop, thread: None };
+        }
+        let stop_thread = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let start = Instant::now();
+            let terminal = std::io::stderr().is_terminal();
+            let mut next = REPORT_INTERVAL;
+            while !stop_thread.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(50));
+                if start.elapsed() >= next {
+                    print_line(&line(start.elapsed()), terminal, false);
+                    next += REPORT_INTERVAL;
+                }
+            }
+            print_line(&line(start.elapsed()), terminal, true);
+        });
+        Progress { stop, thread: Some(thread) }
+    }
+
+    /// Stop the reporter after printing the last line.
+    pub fn finish(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Stop the reporter if it was not finished (error or panic of the worker threads).
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Print a progress line on the standard error.
+/// # Arguments
+/// * `line` - The progress line
+/// * `terminal` - Rewrite the line in place
+/// * `last` - End the line
+fn print_line(line: &str, terminal: bool, last: bool) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = match (terminal, last) {
+        // Clear the end of the previous line
+        (true, false) => write!(stderr, "\r{}\x1b[K", line),
+        (true, true) => writeln!(stderr, "\r{}\x1b[K", line),
+        (false, _) => writeln!(stderr, "{}", line),
+    };
+    let _ = stderr.flush();
+}
+
+/// Counters of the generation, updated by the threads.
+/// # Fields
+/// * `chains` - The number of chains written since the start of the run
+/// * `starts` - The number of start points used since the start of the run, including the chains without distinguished point
+#[derive(Debug, Default)]
+pub struct GenerationCounters {
+    pub chains: AtomicU64,
+    pub starts: AtomicU64,
+}
+
+impl GenerationCounters {
+    /// Get the progress line of the generation.
+    /// # Arguments
+    /// * `elapsed` - The time elapsed since the start of the run
+    /// * `chains` - The number of chains of the table before the run
+    /// * `starts` - The number of start points used before the run
+    /// * `total_starts` - The number of start points of the keyspace
+    /// * `end_starts` - The number of start points used at the end of the generation (coverage)
+    /// * `target` - The number of chains at the end of the generation, if it is given
+    /// * `written` - The size of the table files in bytes
+    /// # Returns
+    /// The chains and their rate, the fraction of the start points used, the bytes written,
+    /// and the time left until the target number of chains or the chains of the last start points.
+    #[allow(clippy::too_many_arguments)]
+    pub fn line(&self, elapsed: Duration, chains: u64, starts: u128, total_starts: u128, end_starts: u128, target: Option<u64>, written: u64) -> String {
+        let run_chains = self.chains.load(Ordering::Relaxed);
+        let run_starts = self.starts.load(Ordering::Relaxed);
+        let used = (starts + run_starts as u128).min(total_starts);
+        let left = end_starts.saturating_sub(used);
+        // The chains of the last start point, in the proportion of the chains kept by this run
+        let last = match run_starts {
+            0 => None,
+            _ => Some(chains + run_chains + (left as f64 * run_chains as f64 / run_starts as f64) as u64),
+        };
+        let (target, eta) = match (target, last) {
+            (_, None) => (target, "?".to_string()),
+            // The target is reached before the last start point
+            (Some(target), Some(last)) if target < last => {
+                let left = target.saturating_sub(chains + run_chains);
+                (Some(target), duration(left as f64 * elapsed.as_secs_f64() / run_chains as f64))
+            },
+            (_, Some(last)) => (Some(last), duration(left as f64 * elapsed.as_secs_f64() / run_starts as f64)),
+        };
+        format!("{} chains ({:.0}/s), {:.2}% of the start points, {} written, ETA {} to {} chains",
+            chains + run_chains,
+            run_chains as f64 / elapsed.as_secs_f64().max(1e-9),
+            100.0 * used as f64 / total_starts.max(1) as f64,
+            bytes(written as f64),
+            eta,
+            target.map(|target| target.to_string()).unwrap_or("?".to_string()))
+    }
+}
+
+/// Counters of the search, updated by the threads.
+/// # Fields
+/// * `table` - The index of the table searched
+/// * `precomputed` - The number of hashs whose candidate endpoints are computed, for the table searched
+/// * `to_precompute` - The number of hashs searched in the table
+/// * `scanned` - The number of bytes of table files read
+/// * `false_alarms` - The number of chains computed again without finding the password
+/// * `found` - The number of hashs found
+#[derive(Debug, Default)]
+pub struct SearchCounters {
+    pub table: AtomicU64,
+    pub precomputed: AtomicU64,
+    pub to_precompute: AtomicU64,
+    pub scanned: AtomicU64,
+    pub false_alarms: AtomicU64,
+    pub found: AtomicU64,
+}
+
+impl SearchCounters {
+    /// Get the progress line of the search.
+    /// # Arguments
+    /// * `hashs` - The number of hashs to search
+    /// * `table_bytes` - The size of the table files read by the search, 0 if they are searched by binary search
+    pub fn line(&self, hashs: usize, table_bytes: u64) -> String {
+        let mut line = format!("Table {}: precomputation {}/{} hashs",
+            self.table.load(Ordering::Relaxed), self.precomputed.load(Ordering::Relaxed), self.to_precompute.load(Ordering::Relaxed));
+        if table_bytes > 0 {
+            let scanned = self.scanned.load(Ordering::Relaxed);
+            line += &format!(", {} of {} scanned", bytes(scanned as f64), bytes(table_bytes as f64));
+        }
+        line + &format!(", {} false alarms, {}/{} hashs found", self.false_alarms.load(Ordering::Relaxed), self.found.load(Ordering::Relaxed), hashs)
+    }
+}
+
+/// Format a duration in seconds as hours, minutes and seconds.
+pub fn duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "?".to_string();
+    }
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m{:02}s", seconds / 3600, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// Parse a duration, in seconds or with units (like 90s, 45m, 6h, 1h30m or 2d).
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let invalid = || format!("Invalid duration: {}", text);
+    if let Ok(seconds) = text.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| invalid());
+    }
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1.0,
+            'm' => 60.0,
+            'h' => 3600.0,
+            'd' => 86400.0,
+            _ => return Err(invalid()),
+        };
+        seconds += number.parse::<f64>().map_err(|_| invalid())? * unit;
+        number.clear();
+    }
+    if text.is_empty() || !number.is_empty() {
+        return Err(invalid());
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generation_line() {
+        let counters = GenerationCounters::default();
+        assert!(counters.line(Duration::from_secs(1), 0, 0, 100, 100, None, 0).contains("ETA ? to ? chains"));
+        // Half of the start points used in 10 s, one chain per two start points
+        counters.starts.store(40, Ordering::Relaxed);
+        counters.chains.store(20, Ordering::Relaxed);
+        let line = counters.line(Duration::from_secs(10), 5, 10, 100, 100, None, 2048);
+        assert_eq!(line, "25 chains (2/s), 50.00% of the start points, 2.0 KiB written, ETA 13s to 50 chains");
+        // Target number of chains, or coverage of the start points
+        assert!(counters.line(Duration::from_secs(10), 5, 10, 100, 100, Some(35), 0).ends_with("ETA 5s to 35 chains"));
+        assert!(counters.line(Duration::from_secs(10), 5, 10, 100, 70, Some(1000), 0).ends_with("ETA 5s to 35 chains"));
+    }
+
+    #[test]
+    fn test_search_line() {
+        let counters = SearchCounters::default();
+        counters.found.store(3, Ordering::Relaxed);
+        assert_eq!(counters.line(10, 0), "Table 0: precomputation 0/0 hashs, 0 false alarms, 3/10 hashs found");
+        assert!(counters.line(10, 1024).contains("0.0 B of 1.0 KiB scanned"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("6h"), Ok(Duration::from_secs(6 * 3600)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("6 hours").is_err());
+        assert!(parse_duration("30m5").is_err());
+        assert!(parse_duration("-1").is_err());
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration(5.4), "5s");
+        assert_eq!(duration(61.0), "1m01s");
+        assert_eq!(duration(3.0 * 3600.0 + 62.0), "3h01m02s");
+        assert_eq!(duration(f64::INFINITY), "?");
+    }
+}
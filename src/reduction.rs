@@ -1,47 +1,360 @@
 //! Module dedicated to the reduction function used in the algorithm.
+//!
+//! Two versions of the reduction function exist, the version of a table is recorded in its manifest:
+//! * version 1 builds each character from a single byte of the hash (`(hash[i + j] + offset) % base`)
+//! * version 2 reads the whole hash as a big integer, mixes in the column and reduces it modulo the keyspace size
+//!
+//! A folder can hold several tables of the same passwords: with version 2, the index of the table is mixed in
+//! with the column, so each table has its own family of reduction functions and its chains are independent of the others.
+//!
+//! The chains of a distinguished-point table use the reduction of the column 0 at every step
+//! and end at the first distinguished point: a password whose keyspace index has its low bits at zero.
 
-/// Function that reduces a hash to a password.
+use std::sync::Arc;
+
+use crate::algorithm::HashAlgorithm;
+use crate::keyspace::Keyspace;
+
+/// Version of the reduction function of the new tables, recorded in the table manifest.
+pub const REDUCTION_VERSION: u32 = 2;
+
+/// The reduction function of a table.
+/// # Fields
+/// * `keyspace` - The passwords to generate
+/// * `version` - The version of the reduction function
+/// * `table` - The index of the table, which perturbs the reduction
+#[derive(Clone, Debug)]
+pub struct Reduction {
+    pub keyspace: Arc<Keyspace>,
+    pub version: u32,
+    pub table: u16,
+}
+
+impl Reduction {
+    /// Create the reduction function of a table.
+    /// # Arguments
+    /// * `keyspace` - The passwords to generate
+    /// * `version` - The version of the reduction function
+    /// * `table` - The index of the table
+    /// # Returns
+    /// The reduction function, or an error if the version is unknown.
+    /// # Note
+    /// Version 1 has a single reduction family: only the first table can use it.
+    pub fn new(keyspace: Arc<Keyspace>, version: u32, table: u16) -> Result<Reduction, String> {
+        if !(1..=REDUCTION_VERSION).contains(&version) {
+            return Err(format!("Unsupported reduction version {} (expected 1 to {})", version, REDUCTION_VERSION));
+        }
+        if version == 1 && table != 0 {
+            return Err(format!("The reduction version 1 cannot build the table {}, only a single table", table));
+        }
+        Ok(Reduction { keyspace, version, table })
+    }
+
+    /// Reduce a hash to a password.
+    /// # Arguments
+    /// * `hash` - The hash to reduce
+    /// * `offset` - The column of the chain
+    /// # Returns
+    /// The password generated from the hash.
+    pub fn reduce(&self, hash: &[u8], offset: u16) -> String {
+        match self.version {
+            1 => reduction(hash, offset, &self.keyspace),
+            _ => self.keyspace.password(reduction_index(hash, offset, self.table, self.keyspace.size())),
+        }
+    }
+
+    /// Reduce a hash to the keyspace index of a password.
+    /// # Arguments
+    /// * `hash` - The hash to reduce
+    /// * `offset` - The column of the chain
+    /// # Returns
+    /// The keyspace index of the password generated from the hash.
+    pub fn reduce_index(&self, hash: &[u8], offset: u16) -> u128 {
+        match self.version {
+            1 => self.keyspace.index(&reduction(hash, offset, &self.keyspace)).unwrap(),
+            _ => reduction_index(hash, offset, self.table, self.keyspace.size()),
+        }
+    }
+
+    /// Walk a chain of a distinguished-point table until its distinguished point.
+    /// # Arguments
+    /// * `hasher` - The hash algorithm
+    /// * `hash` - The hash to reduce first
+    /// * `bits` - The number of low bits at zero of the distinguished points
+    /// * `max_length` - The maximum number of reductions
+    /// # Returns
+    /// The keyspace index of the distinguished point, None if it is not reached after `max_length` reductions.
+    /// # Note
+    /// The password and the digest buffers are reused along the chain.
+    pub fn distinguished_point<H: HashAlgorithm>(&self, hasher: &H, hash: &[u8], bits: u8, max_length: u16) -> Option<u128> {
+        let mut index = self.reduce_index(hash, 0);
+        let mut password = Vec::with_capacity(self.keyspace.max_length);
+        let mut digest = vec![0; hasher.digest_size()];
+        for _ in 1..max_length {
+            if is_distinguished(index, bits) {
+                return Some(index);
+            }
+            self.keyspace.password_into(index, &mut password);
+            hasher.hash_into(&password, &mut digest);
+            index = self.reduce_index(&digest, 0);
+        }
+        Some(index).filter(|&index| is_distinguished(index, bits))
+    }
+}
+
+/// Check if a keyspace index is a distinguished point.
+/// # Arguments
+/// * `index` - The keyspace index
+/// * `bits` - The number of low bits at zero of the distinguished points
+pub fn is_distinguished(index: u128, bits: u8) -> bool {
+    index & ((1 << bits) - 1) == 0
+}
+
+/// Function that reduces a hash to a password (version 1).
 /// # Arguments
 /// * `hash` - A reference to a vector of bytes representing the hash to reduce.
 /// * `offset` - The offset to apply to the hash.
-/// * `password_length` - The length of the password to generate.
+/// * `keyspace` - The passwords to generate (charset and lengths).
 /// # Returns
 /// A string representing the password generated from the hash.
-pub fn reduction(hash: &Vec<u8>, offset: u16, password_length: usize) -> String {
-    let mut password: Vec<u8> = Vec::new(); // The password to generate
+/// # Note
+/// When the keyspace has several lengths, the length is drawn from the last 8 bytes of the hash,
+/// each length being weighted by its number of passwords.
+fn reduction(hash: &[u8], offset: u16, keyspace: &Keyspace) -> String {
+    if keyspace.is_fixed() {
+        return characters(hash, offset, keyspace.max_length, keyspace);
+    }
+    let mut tail = [0; 8];
+    let n = hash.len().min(8);
+    tail[..n].copy_from_slice(&hash[hash.len() - n..]);
+    let index = u64::from_le_bytes(tail).wrapping_add(offset as u64) as u128 % keyspace.size();
+    let (length, _) = keyspace.locate(index);
+    characters(hash, offset, length, keyspace)
+}
 
-    let j = offset / 64;
-    let offset = offset % 64;
+/// Reduce a hash to the characters of a password of a given length.
+/// # Arguments
+/// * `hash` - The hash to reduce
+/// * `offset` - The offset to apply to the hash
+/// * `password_length` - The length of the password to generate
+/// * `keyspace` - The passwords to generate, giving the charset of each position
+fn characters(hash: &[u8], offset: u16, password_length: usize, keyspace: &Keyspace) -> String {
     // 0 <= i < password_length
-    for i in 0..(password_length as u16) {
-        password.push(((hash[((i + j) % 32) as usize] as u16 + offset) % 64) as u8);
-    }
+    (0..password_length).map(|i| {
+        let charset = keyspace.position(i);
+        let base = charset.len();
+        let j = offset as u64 / base;
+        let offset = offset as u64 % base;
+        let byte = hash[((i as u64 + j) % hash.len() as u64) as usize] as u64;
+        // Convert the value to the corresponding character
+        charset.char((byte + offset) % base)
+    }).collect()
+}
 
-    // For each character in the password, convert it to the corresponding character
-    password.iter_mut().for_each(|x| {
-        match x {
-            0..=25 => *x += 65,     // A-Z
-            26..=51 => *x += 71,    // a-z
-            52..=61 => *x -= 4,     // 0-9
-            62 => *x = 33,          // !
-            63 => *x = 42,          // *
-            _ => panic!("Invalid character"), // should never happen
-        }
+/// Reduce a hash to a keyspace index (version 2).
+/// # Arguments
+/// * `hash` - The hash to reduce
+/// * `offset` - The column of the chain
+/// * `table` - The index of the table
+/// * `size` - The number of passwords of the keyspace
+/// # Returns
+/// The keyspace index, lower than `size`.
+/// # Note
+/// The hash is read as a big-endian integer whose 64-bit words are mixed with the column and the previous words
+/// (SplitMix64 of the word XOR a key of the table, the column, the position of the word and the previous mixed word),
+/// then reduced modulo the keyspace size: every byte of the hash changes the password and each column is an unrelated function.
+/// The mixing is a bijection of each 64-bit word, so the modulo bias is at most `size / 2^(8 * hash.len())`,
+/// below 2^-64 for a 128-bit digest and a keyspace of less than 2^64 passwords.
+fn reduction_index(hash: &[u8], offset: u16, table: u16, size: u128) -> u128 {
+    let (rest, _) = hash.chunks(8).enumerate().fold((0u128, 0u64), |(rest, previous), (k, chunk)| {
+        let bits = 8 * chunk.len() as u32;
+        let word = chunk.iter().fold(0u64, |word, &byte| (word << 8) | byte as u64);
+        let mixed = splitmix64(word ^ previous ^ splitmix64(((table as u64) << 48) | ((offset as u64) << 32) | k as u64));
+        let word = mixed & (u64::MAX >> (64 - bits));
+        // The rest is lower than the size: below 2^64, the shifted rest fits in a u128
+        let rest = if size >> 64 == 0 { ((rest << bits) | word as u128) % size } else { append_bits(rest, word, bits, size) };
+        (rest, mixed)
     });
-    return password.iter().map(|x| *x as char).collect();
+    rest
+}
+
+/// Append the bits of a word to a number modulo a size, one bit at a time.
+/// # Arguments
+/// * `rest` - The number, lower than `size`
+/// * `word` - The bits to append
+/// * `bits` - The number of bits of the word
+/// * `size` - The modulus
+/// # Returns
+/// `(rest * 2^bits + word) % size`, without overflowing for the sizes above 2^64.
+fn append_bits(rest: u128, word: u64, bits: u32, size: u128) -> u128 {
+    (0..bits).rev().fold(rest, |rest, bit| {
+        // 2 * rest + 1 < 2 * size: a single subtraction, the lost high bit being compensated by the wrapping
+        let carry = rest >> 127 == 1;
+        let rest = (rest << 1) | ((word >> bit) & 1) as u128;
+        if carry || rest >= size { rest.wrapping_sub(size) } else { rest }
+    })
+}
+
+/// Mix a 64-bit value (SplitMix64 finalizer).
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use sha3::{Digest, Sha3_256};
+    use crate::algorithm::{HashAlgorithm, HashType};
+    use crate::charset::Charset;
+    use crate::mask::Mask;
+
+    /// The passwords of a length with the default charset (`0-9 A-Z a-z ! *`).
+    fn keyspace(length: usize) -> Arc<Keyspace> {
+        Arc::new(Keyspace::fixed(Arc::new(Charset::preset("base64").unwrap()), length).unwrap())
+    }
 
     #[test]
     fn test_reduction() {
         let mut hasher = Sha3_256::new();
         hasher.update("m0000Qa");
         let hash = hasher.finalize().to_vec();
-        
-        println!("{:?}", reduction(&hash, 0, 7));
+
+
+        // Passwords of the reduction before the charsets (A-Z a-z 0-9 ! *), which the tables without manifest need
+        let keyspace = Keyspace::fixed(Charset::legacy(), 7).unwrap();
+        assert_eq!(reduction(&hash, 0, &keyspace), "8OVtatl");
+        assert_eq!(reduction(&hash, 100, &keyspace), "y5R!RJo");
+        assert_eq!(reduction(&hash, 999, &keyspace), "xrn6T!N");
+    }
+
+    #[test]
+    fn test_reduction_charset() {
+        let hash = (0..32).collect::<Vec<u8>>();
+        let digits = Keyspace::fixed(Arc::new(Charset::preset("digits").unwrap()), 19).unwrap();
+        let password = characters(&hash, 0, 40, &digits);
+        assert_eq!(password.len(), 40);
+        assert!(password.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(&password[..12], "012345678901");
+        assert_eq!(reduction(&hash, 0, &digits), password[..19]);
+    }
+
+    #[test]
+    fn test_reduction_lengths() {
+        // 10 passwords of 1 digit for 100 of 2 digits
+        let keyspace = Keyspace::new(Arc::new(Charset::preset("digits").unwrap()), 1, 2).unwrap();
+        let mut lengths = [0; 3];
+        for i in 0..11000u32 {
+            let hash = HashType::Sha3_256.hash(&i.to_le_bytes());
+            let password = reduction(&hash, 0, &keyspace);
+            assert_eq!(keyspace.index(&password).map(|index| keyspace.locate(index).0), Some(password.len()));
+            lengths[password.len()] += 1;
+        }
+        assert!((800..1200).contains(&lengths[1]), "{:?}", lengths);
+    }
+
+    /// Chi-square statistic of the keyspace indexes returned by a function of the hashes of 0, 1, 2...
+    fn chi_square(size: u128, samples: u32, index: impl Fn(&[u8]) -> u128) -> f64 {
+        let mut counts = vec![0u32; size as usize];
+        for i in 0..samples {
+            counts[index(&HashType::Md5.hash(&i.to_le_bytes())) as usize] += 1;
+        }
+        let expected = samples as f64 / size as f64;
+        counts.iter().map(|&count| (count as f64 - expected).powi(2) / expected).sum()
+    }
+
+    #[test]
+    fn test_reduction_uniform() {
+        // 110 passwords of 1 or 2 digits: 109 degrees of freedom, P(chi2 > 170) < 0.0002
+        let keyspace = Arc::new(Keyspace::new(Arc::new(Charset::preset("digits").unwrap()), 1, 2).unwrap());
+        let reduction = Reduction::new(keyspace.clone(), REDUCTION_VERSION, 0).unwrap();
+        for offset in [0, 1, 10, 999] {
+            let chi2 = chi_square(110, 110 * 200, |hash| keyspace.index(&reduction.reduce(hash, offset)).unwrap());
+            assert!(chi2 < 170.0, "column {}: chi2 = {}", offset, chi2);
+        }
+    }
+
+    #[test]
+    fn test_reduction_columns() {
+        // The difference between two columns must not be a constant
+        let keyspace = Arc::new(Keyspace::fixed(Arc::new(Charset::preset("digits").unwrap()), 2).unwrap());
+        let reduction = Reduction::new(keyspace.clone(), REDUCTION_VERSION, 0).unwrap();
+        let index = |hash: &[u8], offset| keyspace.index(&reduction.reduce(hash, offset)).unwrap();
+        let chi2 = chi_square(100, 100 * 200, |hash| (index(hash, 1) + 100 - index(hash, 0)) % 100);
+        assert!(chi2 < 160.0, "chi2 = {}", chi2);
+    }
+
+    #[test]
+    fn test_reduction_all_bytes() {
+        // Every byte of the hash changes the password, not only the first password_length bytes
+        let reduction = Reduction::new(keyspace(7), REDUCTION_VERSION, 0).unwrap();
+        let hash = HashType::Md5.hash(b"password");
+        for k in 0..hash.len() {
+            let mut other = hash.clone();
+            other[k] ^= 1;
+            assert_ne!(reduction.reduce(&hash, 5), reduction.reduce(&other, 5), "byte {}", k);
+        }
+        assert!(Reduction::new(keyspace(7), REDUCTION_VERSION + 1, 0).is_err());
+    }
+
+    #[test]
+    fn test_reduction_large_keyspace() {
+        // 64^20 passwords do not fit in 64 bits
+        let reduction = Reduction::new(keyspace(20), REDUCTION_VERSION, 0).unwrap();
+        let hash = HashType::Sha3_256.hash(b"password");
+        let index = reduction.reduce_index(&hash, 5);
+        assert!(index < reduction.keyspace.size());
+        assert!(index >> 64 != 0);
+        assert_eq!(reduction.keyspace.index(&reduction.reduce(&hash, 5)), Some(index));
+        // The bits are appended as by the u128 arithmetic
+        let (rest, size) = ((1u128 << 69) + 12345, (1u128 << 70) + 3);
+        assert_eq!(append_bits(rest, 0xdead_beef, 32, size), ((rest << 32) | 0xdead_beef) % size);
+        // Modulo 2^128 - 1: (-1) * 2^64 + 2^64 - 1 = -1
+        assert_eq!(append_bits(u128::MAX - 1, u64::MAX, 64, u128::MAX), u128::MAX - 1);
+    }
+
+    #[test]
+    fn test_reduction_mask() {
+        let keyspace = Keyspace::from_mask(Mask::new("?u?l?l?d?d!", &[]).unwrap()).unwrap();
+        for i in 0..1000u32 {
+            let hash = HashType::Sha3_256.hash(&i.to_le_bytes());
+            for offset in [0, 7, 99] {
+                assert!(keyspace.index(&reduction(&hash, offset, &keyspace)).is_some());
+                assert!(keyspace.index(&Reduction::new(Arc::new(keyspace.clone()), REDUCTION_VERSION, 0).unwrap().reduce(&hash, offset)).is_some());
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_reduction_tables() {
+        // The tables of a folder have unrelated reductions
+        let keyspace = keyspace(7);
+        let hash = HashType::Md5.hash(b"password");
+        let tables: Vec<u128> = (0..4).map(|table| Reduction::new(keyspace.clone(), REDUCTION_VERSION, table).unwrap().reduce_index(&hash, 5)).collect();
+        assert!(tables.iter().enumerate().all(|(i, a)| tables[i + 1..].iter().all(|b| a != b)), "{:?}", tables);
+        assert!(Reduction::new(keyspace.clone(), 1, 0).is_ok());
+        assert!(Reduction::new(keyspace, 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_distinguished_point() {
+        assert!(is_distinguished(0, 4));
+        assert!(is_distinguished(48, 4));
+        assert!(!is_distinguished(40, 4));
+        assert!(is_distinguished(40, 0));
+
+        let keyspace = Arc::new(Keyspace::fixed(Arc::new(Charset::preset("digits").unwrap()), 4).unwrap());
+        let reduction = Reduction::new(keyspace.clone(), REDUCTION_VERSION, 0).unwrap();
+        let hash = HashType::Md5.hash(b"1234");
+        let end = reduction.distinguished_point(&HashType::Md5, &hash, 3, 1000).unwrap();
+        assert!(is_distinguished(end, 3));
+        // The chain of the next password ends at the same distinguished point
+        let next = keyspace.password(reduction.reduce_index(&hash, 0));
+        if !is_distinguished(keyspace.index(&next).unwrap(), 3) {
+            assert_eq!(reduction.distinguished_point(&HashType::Md5, &HashType::Md5.hash(next.as_bytes()), 3, 1000), Some(end));
+        }
+        assert_eq!(reduction.distinguished_point(&HashType::Md5, &hash, 13, 1), None);
+    }
+}
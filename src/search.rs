@@ -1,34 +1,130 @@
 //! Implementation of the different search functions used to retrieve the password from the hash
 
 use std::io::Read;
-use std::os::windows::fs::FileExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use rayon::prelude::*;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
 use std::collections::HashMap;
 use std::fs::OpenOptions;
-use crate::hashage::sha3_hash;
-use crate::reduction::reduction;
+use crate::reduction::{is_distinguished, Reduction};
 use crate::hash::Hash;
-use crate::sha3::hash_password;
-use std::time::Instant;
+use crate::algorithm::{HashAlgorithm, HashType};
+use crate::manifest::{check_override, Manifest, MANIFEST_FILE};
+use crate::charset::Charset;
+use crate::keyspace::Keyspace;
+use crate::table::{count_files, open_reader, report_discarded, sorted_path, table_path, SortedTable, TableFormat, CHUNK_CHAINS};
+use crate::progress::{Progress, SearchCounters};
+use std::time::{Duration, Instant};
 
 /// Search the password from the hash.
 /// # Arguments
 /// * `path` - The path to the file containing the passwords
-/// * `chain_length` - The length of the chain
+/// * `chain_length` - The length of the chain, read from the manifest if not given
 /// * `hash` - The hash to search
 /// * `hashs_path` - The path to the file containing the hashs
-/// * `password_length` - The length of the password
-pub fn search_main(path: PathBuf, chain_length: u16, hash: Option<String>, hashs_path: Option<PathBuf>, password_length: usize) {
-    println!("Get hash");
-    let hashs = get_hashs(hash, hashs_path);
-    println!("Generation reduction");
-    let passwords_to_search: Arc<HashMap<Hash, Vec<(String, u16)>>> = Arc::new(generation_reduction(&hashs, chain_length, password_length));
-    println!("Search chains");
-    let start = Instant::now();
-    let hash_founded = search_chains(path, passwords_to_search, password_length);
-    println!("Time elapsed: {:?}", start.elapsed());
+/// * `password_length` - The maximum length of the password, read from the manifest if not given
+/// * `min_length` - The minimum length of the password, read from the manifest if not given
+/// * `hash_type` - The hash algorithm, read from the manifest if not given
+/// * `charset` - The alphabet of the passwords, read from the manifest if not given
+/// * `threads` - The number of threads, the number of CPUs if not given
+/// * `quiet` - Only print the passwords found and the hashs not found
+/// # Returns
+/// The hashs found, or an error if the table cannot be searched with the given parameters.
+/// # Note
+/// When the folder holds several tables, they are searched one after the other with their own reduction function,
+/// a hash found in a table is not searched in the next ones.
+/// In a distinguished-point table, a single chain is computed from each hash (until its distinguished point)
+/// instead of one chain per column.
+/// The table files are read by the threads whatever their number, a table without manifest is searched in all its files.
+/// The progress (precomputation of the hashs, bytes scanned, false alarms and hashs found) is printed every second.
+#[allow(clippy::too_many_arguments)]
+pub fn search_main(path: PathBuf, chain_length: Option<u16>, hash: Option<String>, hashs_path: Option<PathBuf>, password_length: Option<usize>, min_length: Option<usize>, hash_type: Option<HashType>, charset: Option<Charset>, threads: Option<usize>, quiet: bool) -> Result<Vec<Hash>, String> {
+    let threads = threads.unwrap_or_else(num_cpus::get);
+    if threads == 0 {
+        return Err("At least one thread is needed".to_string());
+    }
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| e.to_string())?;
+    let (chain_length, distinguished_bits, reductions, files, format, sorted, hash_type) = match Manifest::read(&path)? {
+        Some(manifest) => {
+            manifest.validate()?;
+            check_override("chain_length", chain_length, manifest.chain_length)?;
+            check_override("password_length", password_length, manifest.keyspace.max_length)?;
+            check_override("min_password_length", min_length, manifest.keyspace.min_length)?;
+            check_override("hash_type", hash_type, manifest.hash)?;
+            check_override("charset", charset.as_ref(), manifest.keyspace.charset.as_ref())?;
+            let reductions = (0..manifest.tables).map(|table| manifest.reduction(table)).collect::<Result<Vec<Reduction>, String>>()?;
+            (manifest.chain_length, manifest.distinguished_bits, reductions, manifest.files, manifest.format, manifest.sorted, manifest.hash)
+        },
+        // Table generated before the manifests: the parameters must be given
+        None => match (chain_length, password_length) {
+            (Some(chain_length), Some(password_length)) => {
+                // The alphabet of the reduction of these tables, in its order
+                let charset = charset.map(Arc::new).unwrap_or_else(Charset::legacy);
+                let keyspace = Arc::new(Keyspace::new(charset, min_length.unwrap_or(password_length), password_length)?);
+                // Reduction function and files of the tables generated before the manifests
+                let files = count_files(&path, TableFormat::Text);
+                if files == 0 {
+                    return Err(format!("No table file in {}", path.display()));
+                }
+                (chain_length, 0, vec![Reduction::new(keyspace, 1, 0)?], files, TableFormat::Text, false, hash_type.unwrap_or(HashType::Sha3_256))
+            },
+            _ => return Err(format!("No {} in {}: the chain length and the password length must be given", MANIFEST_FILE, path.display())),
+        },
+    };
+
+    if !quiet {
+        println!("Get hash");
+    }
+    let hashs = get_hashs(hash, hashs_path, hash_type.digest_size())?;
+
+    // Progress of the search, the sorted tables are not scanned
+    let table_bytes = match sorted {
+        true => 0,
+        false => reductions.iter().flat_map(|reduction| (0..files).map(|t| table_path(&path, reduction.table, t, format)))
+            .filter_map(|file| std::fs::metadata(file).ok()).map(|metadata| metadata.len()).sum(),
+    };
+    let counters = Arc::new(SearchCounters::default());
+    let counters_progress = counters.clone();
+    let total = hashs.len();
+    let progress = Progress::start(quiet, move |_| counters_progress.line(total, table_bytes));
+
+    let mut hash_founded = Vec::new();
+    let mut elapsed = Duration::ZERO;
+    pool.install(|| {
+        for reduction in reductions.iter() {
+            // The hashs found in a previous table are not searched again
+            let hashs_to_search: Vec<Hash> = hashs.iter().filter(|hash| !hash_founded.contains(*hash)).cloned().collect();
+            if hashs_to_search.is_empty() {
+                break;
+            }
+            counters.table.store(reduction.table as u64, Ordering::Relaxed);
+            counters.precomputed.store(0, Ordering::Relaxed);
+            counters.to_precompute.store(hashs_to_search.len() as u64, Ordering::Relaxed);
+            if distinguished_bits > 0 {
+                let endpoints = distinguished_reduction(&hash_type, &hashs_to_search, chain_length, distinguished_bits, reduction, &counters);
+                let start = Instant::now();
+                let chains = find_chains(&hash_type, &path, &endpoints, reduction, files, format, sorted, &counters)?;
+                hash_founded.extend(search_distinguished(&hash_type, &endpoints, &chains, chain_length, distinguished_bits, reduction, &counters));
+                elapsed += start.elapsed();
+                continue;
+            }
+            // The reduced passwords depend on the reduction function of the table
+            let passwords_to_search: Arc<HashMap<Hash, Vec<(u128, u16)>>> = Arc::new(generation_reduction(&hash_type, &hashs_to_search, chain_length, reduction, &counters));
+            let start = Instant::now();
+            hash_founded.extend(if sorted {
+                search_sorted(&hash_type, path.clone(), passwords_to_search, reduction, &counters)?
+            } else {
+                search_chains(&hash_type, path.clone(), passwords_to_search, reduction, files, format, &counters)?
+            });
+            elapsed += start.elapsed();
+        }
+        Ok::<(), String>(())
+    })?;
+    progress.finish();
+    if !quiet {
+        println!("Time elapsed: {:?}", elapsed);
+    }
 
     // For each hash, check if it has been found
     for hash in hashs.iter() {
@@ -37,49 +133,58 @@ pub fn search_main(path: PathBuf, chain_length: u16, hash: Option<String>, hashs
             println!("Hash not found: {:?}", hash);
         }
     }
+    Ok(hash_founded)
 }
 
 /// Get the hashs from the hash or the hashs file.
 /// # Arguments
 /// * `hash` - The hash to search
 /// * `hashs_path` - The path to the file containing the hashs
+/// * `digest_size` - The size of the digests of the hash algorithm
 /// # Returns
-/// A vector containing the hashs.
-fn get_hashs(hash: Option<String>, hashs_path: Option<PathBuf>) -> Vec<Hash> {
+/// A vector containing the hashs, or an error if the file cannot be read or a hash is invalid or does not have the size of the digests.
+fn get_hashs(hash: Option<String>, hashs_path: Option<PathBuf>, digest_size: usize) -> Result<Vec<Hash>, String> {
     let mut hashs = Vec::new(); // Vector containing the hashs
     // If the hash is not None, add it to the vector
     if let Some(hash) = hash {
-        hashs.push(Hash::from(hash));
+        hashs.push(Hash::try_from(hash.as_str())?);
     }
     // If the hashs_path is not None, add the hashs from the file to the vector
     else if let Some(hashs_path) = hashs_path {
         // Open the file in read-only mode
         let mut file = OpenOptions::new()
             .read(true)
-            .open(hashs_path.clone())
-            .unwrap();
+            .open(&hashs_path)
+            .map_err(|e| format!("Cannot open {}: {}", hashs_path.display(), e))?;
 
         // Read the file contents into a string
         let mut buf = String::new();
-        file.read_to_string(&mut buf).unwrap();
+        file.read_to_string(&mut buf).map_err(|e| format!("Cannot read {}: {}", hashs_path.display(), e))?;
         
-        hashs = buf.split("\n").collect::<Vec<&str>>().iter().map(|hash| {
-            Hash::from(hash.to_string())
-        }).collect::<Vec<Hash>>();
+        hashs = buf.lines().filter(|hash| !hash.trim().is_empty()).map(|hash| {
+            Hash::try_from(hash)
+        }).collect::<Result<Vec<Hash>, String>>()?;
+    }
+
+    // Check the size of the hashs
+    if let Some(hash) = hashs.iter().find(|hash| hash.hash.len() != digest_size) {
+        return Err(format!("{} is not a {}-byte digest", hash, digest_size));
     }
-    hashs
+    Ok(hashs)
 }
 
 /// Generate the reduced passwords from the hashs.
 /// # Arguments
+/// * `hasher` - The hash algorithm
 /// * `hashs` - The hashs to generate the reduced passwords
 /// * `chain_length` - The length of the chain
-/// * `password_length` - The length of the password
+/// * `reduction` - The reduction function of the table
+/// * `counters` - The progress of the search, counting the hashs precomputed
 /// # Returns
-/// A hashmap containing the hashs and the reduced passwords.
-fn generation_reduction(hashs: &Vec<Hash>, chain_length: u16, password_length: usize) -> HashMap<Hash, Vec<(String, u16)>> {
+/// A hashmap containing the hashs and the keyspace indexes of the reduced passwords.
+fn generation_reduction<H: HashAlgorithm>(hasher: &H, hashs: &[Hash], chain_length: u16, reduction: &Reduction, counters: &SearchCounters) -> HashMap<Hash, Vec<(u128, u16)>> {
     // Initialize a hashmap containing the hashs and the reduced passwords
-    let reduced_passwords: Mutex<HashMap<Hash, Vec<(String, u16)>>> = Mutex::new(HashMap::new());
+    let reduced_passwords: Mutex<HashMap<Hash, Vec<(u128, u16)>>> = Mutex::new(HashMap::new());
 
     // For each hash, generate the reduced passwords
     hashs.par_iter().for_each(|hash| {
@@ -89,16 +194,17 @@ fn generation_reduction(hashs: &Vec<Hash>, chain_length: u16, password_length: u
             let mut hash_to_red: Vec<u8> = hash.hash.clone();
             let mut password: String;
             for offset in (2..=length).rev() {
-                password = reduction(&hash_to_red, chain_length - offset, password_length);
-                hash_to_red = hash_password(&password);
+                password = reduction.reduce(&hash_to_red, chain_length - offset);
+                hash_to_red = hasher.hash(password.as_bytes());
             }
             // Generate the reduced password
-            password = reduction(&hash_to_red, chain_length - 1, password_length);
+            let index = reduction.reduce_index(&hash_to_red, chain_length - 1);
             // Add the reduced password to the hashmap
-            reducted_passwords_local.push((password.clone(), chain_length - length));
+            reducted_passwords_local.push((index, chain_length - length));
         }
         // Add the reduced passwords to the hashmap
         reduced_passwords.lock().unwrap().insert(hash.clone(), reducted_passwords_local);
+        counters.precomputed.fetch_add(1, Ordering::Relaxed);
     });
 
     reduced_passwords.into_inner().unwrap()
@@ -106,37 +212,34 @@ fn generation_reduction(hashs: &Vec<Hash>, chain_length: u16, password_length: u
 
 /// Search the chains to retrieve the password from the hash.
 /// # Arguments
+/// * `hash_type` - The hash algorithm of the table
 /// * `path` - The path to the file containing the passwords
 /// * `passwords_to_search` - The passwords to search
-/// * `password_length` - The length of the password
+/// * `reduction` - The reduction function of the table, giving its index
+/// * `files` - The number of table files
+/// * `format` - The format of the table files
+/// * `counters` - The progress of the search, counting the bytes scanned, the false alarms and the hashs found
 /// # Returns
-/// A vector containing the hashs found.
-fn search_chains(path: PathBuf, passwords_to_search: Arc<HashMap<Hash, Vec<(String, u16)>>>, password_length: usize) -> Vec<Hash> {
-    // Get the number of available CPUs of the current system   
-    let thread = num_cpus::get() as u64;
-    
+/// A vector containing the hashs found, or an error if a table file cannot be read.
+#[allow(clippy::too_many_arguments)]
+fn search_chains(hash_type: &HashType, path: PathBuf, passwords_to_search: Arc<HashMap<Hash, Vec<(u128, u16)>>>, reduction: &Reduction, files: u64, format: TableFormat, counters: &SearchCounters) -> Result<Vec<Hash>, String> {
     // Initialize a vector containing the hashs found
     let hash_founded: Mutex<Vec<Hash>> = Mutex::new(Vec::new());
 
     // For each thread, search the chains
-    (0..thread).into_par_iter().for_each(|t| {
-        let path = path.clone().to_str().unwrap().to_string();
-        let file = OpenOptions::new()
-        .read(true)
-        .open(path.clone() + format!("test_{}.txt", t).as_str())
-        .unwrap();
-
-        let c = (2 * password_length + 1) * 100000;
-
-        let mut buf = vec![0; c];
-        let mut offset = 0;
-        // Read the file and search the password
-        while file.seek_read(&mut buf, offset).unwrap() != 0 {
-            let contents = String::from_utf8(buf.to_vec()).unwrap();
-            let mut passwords = contents.split("\n").collect::<Vec<&str>>();
-            passwords.pop();
-            let passwords: HashMap<String, String> = passwords.into_par_iter().map(|p| (p[password_length..].to_string(), p[0..password_length].to_string())).collect();
-            
+    (0..files).into_par_iter().try_for_each(|t| {
+        let file = table_path(&path, reduction.table, t, format);
+        let mut reader = open_reader(&file, format, *hash_type, &reduction.keyspace, CHUNK_CHAINS).map_err(|e| format!("Cannot open {}: {}", file.display(), e))?;
+
+        // Read the file chunk by chunk and search the password
+        let mut scanned = 0;
+        while let Some(chunk) = reader.next_chunk().map_err(|e| format!("Cannot read {}: {}", file.display(), e))? {
+            counters.scanned.fetch_add(reader.bytes_read() - scanned, Ordering::Relaxed);
+            scanned = reader.bytes_read();
+            let passwords: HashMap<u128, u128> = chunk.into_par_iter()
+                .map(|chain| (chain.end, chain.start))
+                .collect();
+
             if hash_founded.lock().unwrap().len() == passwords_to_search.len() {
                 break;
             }
@@ -147,43 +250,202 @@ fn search_chains(path: PathBuf, passwords_to_search: Arc<HashMap<Hash, Vec<(Stri
                     if hash_founded.lock().unwrap().contains(hash) {
                         break;
                     }
-                    if passwords.contains_key(password) { 
-                        if let Some(reduc) = test_reduction(passwords.get(password).unwrap().clone(), hash.clone(), *offset as u32, password_length) {
+                    if let Some(start) = passwords.get(password) {
+                        if let Some(reduc) = test_reduction(hash_type, reduction.keyspace.password(*start), hash.clone(), *offset as u32, reduction) {
                             if !hash_founded.lock().unwrap().contains(hash) {
                                 hash_founded.lock().unwrap().push(hash.clone());
+                                counters.found.fetch_add(1, Ordering::Relaxed);
                                 println!("hash found: {:?} password: {}", hash, reduc);
                             }
                             break;
                         }
+                        counters.false_alarms.fetch_add(1, Ordering::Relaxed);
                     }
                 }
             });
-            offset += c as u64;
         }
+        report_discarded(&file, reader.as_ref());
+        Ok::<(), String>(())
+    })?;
+    Ok(hash_founded.into_inner().unwrap())
+}
+
+/// Search the chains in the table sorted by endpoint to retrieve the password from the hash.
+/// # Arguments
+/// * `hash_type` - The hash algorithm of the table
+/// * `path` - The folder containing the rainbow table
+/// * `passwords_to_search` - The passwords to search
+/// * `reduction` - The reduction function of the table, giving its index
+/// * `counters` - The progress of the search, counting the false alarms and the hashs found
+/// # Returns
+/// A vector containing the hashs found, or an error if the sorted table cannot be read.
+/// # Note
+/// Each reduced password is looked up by binary search instead of scanning the whole table.
+fn search_sorted(hash_type: &HashType, path: PathBuf, passwords_to_search: Arc<HashMap<Hash, Vec<(u128, u16)>>>, reduction: &Reduction, counters: &SearchCounters) -> Result<Vec<Hash>, String> {
+    let file = sorted_path(&path, reduction.table);
+    let found = passwords_to_search.par_iter().map(|(hash, password_list)| {
+        let mut table = SortedTable::open(&file, *hash_type, &reduction.keyspace).map_err(|e| format!("Cannot open {}: {}", file.display(), e))?;
+        for (password, offset) in password_list.iter().rev() {
+            let starts = table.find(*password).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+            for start in starts {
+                if let Some(reduc) = test_reduction(hash_type, reduction.keyspace.password(start), hash.clone(), *offset as u32, reduction) {
+                    counters.found.fetch_add(1, Ordering::Relaxed);
+                    println!("hash found: {:?} password: {}", hash, reduc);
+                    return Ok(Some(hash.clone()));
+                }
+                counters.false_alarms.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(None)
+    }).collect::<Result<Vec<Option<Hash>>, String>>()?;
+    Ok(found.into_iter().flatten().collect())
+}
 
+/// Walk the chains of the hashs until their distinguished point.
+/// # Arguments
+/// * `hasher` - The hash algorithm
+/// * `hashs` - The hashs to search
+/// * `chain_length` - The maximum length of the chains
+/// * `bits` - The number of low bits at zero of the distinguished points
+/// * `reduction` - The reduction function of the table
+/// * `counters` - The progress of the search, counting the hashs precomputed
+/// # Returns
+/// A hashmap containing the distinguished points and the hashs leading to them.
+/// # Note
+/// A hash which does not reach a distinguished point within `chain_length` passwords is in none of the chains of the table.
+fn distinguished_reduction<H: HashAlgorithm>(hasher: &H, hashs: &[Hash], chain_length: u16, bits: u8, reduction: &Reduction, counters: &SearchCounters) -> HashMap<u128, Vec<Hash>> {
+    let points: Vec<(u128, Hash)> = hashs.par_iter().filter_map(|hash| {
+        let point = reduction.distinguished_point(hasher, &hash.hash, bits, chain_length).map(|end| (end, hash.clone()));
+        counters.precomputed.fetch_add(1, Ordering::Relaxed);
+        point
+    }).collect();
 
-    });
-    hash_founded.into_inner().unwrap()
+    let mut endpoints: HashMap<u128, Vec<Hash>> = HashMap::new();
+    for (end, hash) in points {
+        endpoints.entry(end).or_default().push(hash);
+    }
+    endpoints
+}
+
+/// Find the start points of the chains ending at some endpoints.
+/// # Arguments
+/// * `hash_type` - The hash algorithm of the table
+/// * `path` - The folder containing the rainbow table
+/// * `endpoints` - The endpoints to find
+/// * `reduction` - The reduction function of the table, giving its index
+/// * `files` - The number of table files
+/// * `format` - The format of the table files
+/// * `sorted` - Search the table sorted by endpoint instead of the table files
+/// * `counters` - The progress of the search, counting the bytes scanned
+/// # Returns
+/// A hashmap containing the endpoints found and the start points of their chains, or an error if the table cannot be read.
+#[allow(clippy::too_many_arguments)]
+fn find_chains(hash_type: &HashType, path: &Path, endpoints: &HashMap<u128, Vec<Hash>>, reduction: &Reduction, files: u64, format: TableFormat, sorted: bool, counters: &SearchCounters) -> Result<HashMap<u128, Vec<u128>>, String> {
+    let chains: Mutex<HashMap<u128, Vec<u128>>> = Mutex::new(HashMap::new());
+    if sorted {
+        let file = sorted_path(path, reduction.table);
+        let mut table = SortedTable::open(&file, *hash_type, &reduction.keyspace).map_err(|e| format!("Cannot open {}: {}", file.display(), e))?;
+        for end in endpoints.keys() {
+            let starts = table.find(*end).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+            if !starts.is_empty() {
+                chains.lock().unwrap().insert(*end, starts);
+            }
+        }
+    } else {
+        (0..files).into_par_iter().try_for_each(|t| {
+            let file = table_path(path, reduction.table, t, format);
+            let mut reader = open_reader(&file, format, *hash_type, &reduction.keyspace, CHUNK_CHAINS).map_err(|e| format!("Cannot open {}: {}", file.display(), e))?;
+            let mut scanned = 0;
+            while let Some(chunk) = reader.next_chunk().map_err(|e| format!("Cannot read {}: {}", file.display(), e))? {
+                counters.scanned.fetch_add(reader.bytes_read() - scanned, Ordering::Relaxed);
+                scanned = reader.bytes_read();
+                for chain in chunk.into_iter().filter(|chain| endpoints.contains_key(&chain.end)) {
+                    chains.lock().unwrap().entry(chain.end).or_default().push(chain.start);
+                }
+            }
+            report_discarded(&file, reader.as_ref());
+            Ok::<(), String>(())
+        })?;
+    }
+    Ok(chains.into_inner().unwrap())
+}
+
+/// Search the chains of a distinguished-point table to retrieve the passwords from the hashs.
+/// # Arguments
+/// * `hasher` - The hash algorithm
+/// * `endpoints` - The distinguished points and the hashs leading to them
+/// * `chains` - The distinguished points found in the table and the start points of their chains
+/// * `chain_length` - The maximum length of the chains
+/// * `bits` - The number of low bits at zero of the distinguished points
+/// * `reduction` - The reduction function of the table
+/// * `counters` - The progress of the search, counting the false alarms and the hashs found
+/// # Returns
+/// A vector containing the hashs found.
+#[allow(clippy::too_many_arguments)]
+fn search_distinguished<H: HashAlgorithm>(hasher: &H, endpoints: &HashMap<u128, Vec<Hash>>, chains: &HashMap<u128, Vec<u128>>, chain_length: u16, bits: u8, reduction: &Reduction, counters: &SearchCounters) -> Vec<Hash> {
+    endpoints.par_iter().flat_map(|(end, hashs)| {
+        let starts = chains.get(end).map(|starts| starts.as_slice()).unwrap_or_default();
+        hashs.iter().filter_map(|hash| {
+            // A chain merged with another one can end at the point without containing the hash (false alarm)
+            let password = starts.iter().find_map(|&start| {
+                let password = test_distinguished(hasher, start, hash, chain_length, bits, reduction);
+                if password.is_none() {
+                    counters.false_alarms.fetch_add(1, Ordering::Relaxed);
+                }
+                password
+            })?;
+            counters.found.fetch_add(1, Ordering::Relaxed);
+            println!("hash found: {:?} password: {}", hash, password);
+            Some(hash.clone())
+        }).collect::<Vec<Hash>>()
+    }).collect()
+}
+
+/// Walk a chain of a distinguished-point table from its start point until its distinguished point.
+/// # Arguments
+/// * `hasher` - The hash algorithm
+/// * `start` - The keyspace index of the start point
+/// * `hash` - The hash to compare
+/// * `chain_length` - The maximum length of the chains
+/// * `bits` - The number of low bits at zero of the distinguished points
+/// * `reduction` - The reduction function of the table
+/// # Returns
+/// The password of the chain whose hash is `hash`, None if the chain does not contain it.
+fn test_distinguished<H: HashAlgorithm>(hasher: &H, start: u128, hash: &Hash, chain_length: u16, bits: u8, reduction: &Reduction) -> Option<String> {
+    let mut index = start;
+    for _ in 0..chain_length {
+        let password = reduction.keyspace.password(index);
+        let hash_password = hasher.hash(password.as_bytes());
+        if hash_password == hash.hash {
+            return Some(password);
+        }
+        index = reduction.reduce_index(&hash_password, 0);
+        if is_distinguished(index, bits) {
+            return None;
+        }
+    }
+    None
 }
 
 /// Test the reduction function.
 /// # Arguments
+/// * `hasher` - The hash algorithm
 /// * `reduc` - The password to reduce
 /// * `hash` - The hash to compare
 /// * `offset` - The offset to reduce
-/// * `password_length` - The length of the password
+/// * `reduction` - The reduction function of the table
 /// # Returns
 /// The reduced password if the hash is found, None otherwise.
-fn test_reduction(reduc: String, hash: Hash, offset: u32, password_length: usize) -> Option<String> {
+fn test_reduction<H: HashAlgorithm>(hasher: &H, reduc: String, hash: Hash, offset: u32, reduction: &Reduction) -> Option<String> {
     let mut reduc = reduc.clone();
     // 0 <= i < offset
     for i in 0..offset {
         // Initialize a vector containing the hash of the reduced password
-        let hash_str: Vec<u8> = sha3_hash(&reduc, Some(256)).hash;
-        reduc = reduction(&hash_str, i as u16, password_length);
+        let hash_str: Vec<u8> = hasher.hash(reduc.as_bytes());
+        reduc = reduction.reduce(&hash_str, i as u16);
     }
     // If the hash of the reduced password is equal to the hash, return the reduced password
-    if sha3_hash(&reduc, Some(256)) == hash {
+    if Hash::new(hasher.hash(reduc.as_bytes())) == hash {
         return Some(reduc);
     }
     None
@@ -191,55 +453,137 @@ fn test_reduction(reduc: String, hash: Hash, offset: u32, password_length: usize
 
 #[cfg(test)]
 mod tests {
-    use std::io::Write;
-
     use super::*;
-    use rand::Rng;
+    use crate::generation::{generation_main, StopConditions};
     use crate::password::Password;
+    use crate::reduction::REDUCTION_VERSION;
+    use crate::table::{open_writer, Chain};
+    use rand::Rng;
+
+    /// The passwords of a length with the default charset (`0-9 A-Z a-z ! *`).
+    fn keyspace(length: usize) -> Arc<Keyspace> {
+        Arc::new(Keyspace::fixed(Arc::new(Charset::preset("base64").unwrap()), length).unwrap())
+    }
 
     #[test]
     fn test_generation_reduction() {
-        let hashs = vec![
-            Hash::from("bf35db71c337cf0701082699459d12442e5e27ba1cf2fb4eae2cafad41c45d2e".to_string()),
-        ];
         let chain_length = 100;
-        let password_length = 4;
-        println!("{:#?}", generation_reduction(&hashs, chain_length, password_length));
+        let reduction = Reduction::new(keyspace(4), REDUCTION_VERSION, 0).unwrap();
+        // Walk a chain, keeping the hash of its 10th password
+        let mut password = "0000".to_string();
+        let mut passwords = Vec::new();
+        let mut hash = Vec::new();
+        for offset in 0..chain_length {
+            passwords.push(password.clone());
+            let hash_tmp = HashType::Sha3_256.hash(password.as_bytes());
+            if offset == 10 {
+                hash = hash_tmp.clone();
+            }
+            password = reduction.reduce(&hash_tmp, offset);
+        }
+        let hash = Hash::new(hash);
+
+        // The end of the chain is reached from the hash, which is found again from the start of the chain
+        let reduced_passwords = generation_reduction(&HashType::Sha3_256, std::slice::from_ref(&hash), chain_length, &reduction, &SearchCounters::default());
+        assert_eq!(reduced_passwords[&hash].len(), chain_length as usize);
+        assert!(reduced_passwords[&hash].contains(&(reduction.keyspace.index(&password).unwrap(), 10)));
+        assert_eq!(test_reduction(&HashType::Sha3_256, "0000".to_string(), hash, 10, &reduction), Some(passwords[10].clone()));
+    }
+
+    #[test]
+    fn test_search_distinguished() {
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-distinguished");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let keyspace = Arc::new(Keyspace::fixed(Arc::new(Charset::preset("digits").unwrap()), 4).unwrap());
+        let reduction = Reduction::new(keyspace.clone(), REDUCTION_VERSION, 0).unwrap();
+
+        // Chains of 16 passwords on average, at most 200
+        let mut writer = open_writer(&table_path(&dir, 0, 0, TableFormat::Binary), TableFormat::Binary, HashType::Md5, &keyspace).unwrap();
+        for start in (0..10000).step_by(50) {
+            let hash = HashType::Md5.hash(keyspace.password(start).as_bytes());
+            if let Some(end) = reduction.distinguished_point(&HashType::Md5, &hash, 4, 200) {
+                writer.write_chain(&Chain { start, end }).unwrap();
+            }
+        }
+        writer.sync().unwrap();
+        drop(writer);
+
+        // The third password of the chain of 0050
+        let mut index = 50;
+        for _ in 0..2 {
+            index = reduction.reduce_index(&HashType::Md5.hash(keyspace.password(index).as_bytes()), 0);
+        }
+        let hashs = vec![Hash::new(HashType::Md5.hash(keyspace.password(index).as_bytes()))];
+        let counters = SearchCounters::default();
+        let endpoints = distinguished_reduction(&HashType::Md5, &hashs, 200, 4, &reduction, &counters);
+        let chains = find_chains(&HashType::Md5, &dir, &endpoints, &reduction, 1, TableFormat::Binary, false, &counters).unwrap();
+        assert_eq!(search_distinguished(&HashType::Md5, &endpoints, &chains, 200, 4, &reduction, &counters), hashs);
+        assert_eq!(counters.precomputed.load(Ordering::Relaxed), 1);
+        assert_eq!(counters.scanned.load(Ordering::Relaxed), std::fs::metadata(table_path(&dir, 0, 0, TableFormat::Binary)).unwrap().len());
+        assert_eq!(counters.found.load(Ordering::Relaxed), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
     fn test_search_main() {
-        let path = PathBuf::from("./output/");
-        let password_length = 5;
-        let chain_length = 100;
-        let hash = None;
-        let hashs_path = Some(PathBuf::from("./hashs.txt"));
-        search_main(path, chain_length, hash, hashs_path, password_length);
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-search");
+        let _ = std::fs::remove_dir_all(&dir);
+        // Two table files searched by three threads
+        generation_main(Some(dir.clone()), true, 50, 0, Arc::unwrap_or_clone(keyspace(2)), 1, TableFormat::Text, false, HashType::Sha1, Some(2), true, StopConditions::default()).unwrap();
+
+        // The second start points of the two files (00 + 35 and 01 + 35), and a password out of the table
+        let hashs: Vec<Hash> = [35, 36].iter().map(|&index| Password::from_index(index, &keyspace(2)).unwrap().to_string()).chain(["abc".to_string()])
+            .map(|password| Hash::new(HashType::Sha1.hash(password.as_bytes())))
+            .collect();
+        let hashs_path = dir.join("hashs.txt");
+        std::fs::write(&hashs_path, hashs.iter().map(|hash| format!("{:?}\n", hash)).collect::<String>()).unwrap();
+        // The parameters are read from the manifest
+        let mut found = search_main(dir.clone(), None, None, Some(hashs_path), None, None, None, None, Some(3), true).unwrap();
+        found.sort_by_key(|hash| hashs.iter().position(|h| h == hash));
+        assert_eq!(found, hashs[..2]);
+        assert!(search_main(dir.clone(), Some(100), Some(hashs[0].to_string()), None, None, None, None, None, None, true).is_err());
+        assert!(search_main(dir.clone(), None, Some(hashs[0].to_string()), None, None, None, None, None, Some(0), true).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-    
+
+    #[test]
+    fn test_search_tables() {
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-search-tables");
+        let _ = std::fs::remove_dir_all(&dir);
+        generation_main(Some(dir.clone()), true, 50, 0, Arc::unwrap_or_clone(keyspace(2)), 2, TableFormat::Text, false, HashType::Sha1, None, true, StopConditions::default()).unwrap();
+        let manifest = Manifest::read(&dir).unwrap().unwrap();
+        assert_eq!(manifest.tables, 2);
+        assert!(table_path(&dir, 1, 0, TableFormat::Text).exists());
+
+        // The start points are shared by the tables, found whatever the table
+        let hash = Hash::new(HashType::Sha1.hash(Password::from_index(35, &keyspace(2)).unwrap().to_string().as_bytes()));
+        assert_eq!(search_main(dir.clone(), None, Some(hash.to_string()), None, None, None, None, None, None, true).unwrap(), vec![hash]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
-    fn test_reduction() {
-        // genere n hash de mot de passe de l caractères
+    fn test_get_hashs() {
+        // Hashs of n random passwords of l characters
         let l = 4;
         let n = 100;
+        let keyspace = keyspace(l);
+        let hashs: Vec<Hash> = (0..n).map(|_| {
+            let password = Password::from_index(rand::thread_rng().gen_range(0..keyspace.size()), &keyspace).unwrap();
+            Hash::new(HashType::Sha3_256.hash(password.to_string().as_bytes()))
+        }).collect();
 
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open("hashs.txt")
-            .unwrap();
-
-        for _ in 0..n-1 {
-            let password = Password::from_b64(vec![0; l].iter().map(|_| rand::thread_rng().gen_range(0..=63) as u64).collect::<Vec<u64>>());
-            let hash = sha3_hash(&password.password, Some(256));
-            file.write_all(format!("{:?}\n", hash).as_bytes()).unwrap();
-            println!("{:?} - {:?}", password.password, hash);
-        }
-        let password = Password::from_b64(vec![0; l].iter().map(|_| rand::thread_rng().gen_range(0..=63) as u64).collect::<Vec<u64>>());
-        let hash = sha3_hash(&password.password, Some(256));
-        file.write_all(format!("{:?}", hash).as_bytes()).unwrap();
+        let hashs_path = std::env::temp_dir().join("arc-en-ciel-test-hashs.txt");
+        std::fs::write(&hashs_path, hashs.iter().map(|hash| format!("{:?}\n", hash)).collect::<String>()).unwrap();
+        assert_eq!(get_hashs(None, Some(hashs_path.clone()), 32).unwrap(), hashs);
+        assert_eq!(get_hashs(Some(hashs[0].to_string()), None, 32).unwrap(), hashs[..1]);
 
-        file.sync_all().unwrap();
-        file.flush().unwrap();
+        // Digests of another size, invalid hashs and missing file
+        assert!(get_hashs(Some(hashs[0].to_string()), None, 16).is_err());
+        assert!(get_hashs(Some("xyz".to_string()), None, 32).is_err());
+        std::fs::write(&hashs_path, "abc\n").unwrap();
+        assert!(get_hashs(None, Some(hashs_path.clone()), 32).is_err());
+        std::fs::remove_file(&hashs_path).unwrap();
+        assert!(get_hashs(None, Some(hashs_path), 32).is_err());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,263 @@
+//! Sort the chains of a rainbow table by endpoint, so the search can find them by binary search.
+//! The sort can also remove the merged chains (chains sharing an endpoint) to build a perfect table.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::checkpoint::CHECKPOINT_FILE;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::table::{open_reader, open_writer, report_discarded, sorted_path, table_path, Chain, TableFormat, TableReader, SORTED_FILE};
+
+/// Default number of chains sorted in memory at once.
+pub const RUN_CHAINS: usize = 1 << 22;
+
+/// Sort the rainbow table by endpoint (main function).
+/// # Arguments
+/// * `path` - The folder containing the rainbow table
+/// * `run_chains` - The number of chains sorted in memory at once
+/// * `perfect` - Only keep one chain per endpoint
+/// # Returns
+/// An error if the table cannot be read or written.
+/// # Note
+/// The chains of every file of a table are merged in a single binary file sorted by endpoint (`sorted.bin`, `sorted_{table}.bin`).
+/// Tables larger than the memory are sorted by runs of `run_chains` chains, written in temporary files and then merged.
+/// In a perfect table, the chain with the smallest start point is kept for each endpoint.
+/// The table files and the checkpoint are then removed, the sorted table replacing them.
+pub fn sort_main(path: PathBuf, run_chains: usize, perfect: bool) -> Result<(), String> {
+    let mut manifest = Manifest::read(&path)?.ok_or(format!("No {} in {}", MANIFEST_FILE, path.display()))?;
+    manifest.validate()?;
+    if manifest.perfect {
+        println!("The table is already perfect, its chains are only in {}", SORTED_FILE);
+        return Ok(());
+    }
+
+    // Sort each table
+    let mut counts = Vec::new();
+    for table in 0..manifest.tables {
+        let (read, chains) = sort_table(&path, &manifest, table, run_chains, perfect).map_err(|e| e.to_string())?;
+        if manifest.tables > 1 {
+            println!("Table {}:", table);
+        }
+        println!("{} chains sorted", chains);
+        if perfect {
+            println!("{} merged chains removed", read - chains);
+        }
+        counts.push(chains);
+    }
+
+    manifest.sorted = true;
+    if !perfect {
+        return manifest.write(&path).map_err(|e| e.to_string());
+    }
+
+    // The sorted tables replace the table files once the manifest describes them
+    manifest.perfect = true;
+    manifest.chains = counts;
+    manifest.write(&path).map_err(|e| e.to_string())?;
+    for table in 0..manifest.tables {
+        for i in 0..manifest.files {
+            remove_if_exists(&table_path(&path, table, i, manifest.format)).map_err(|e| e.to_string())?;
+        }
+    }
+    remove_if_exists(&path.join(CHECKPOINT_FILE)).map_err(|e| e.to_string())
+}
+
+/// Sort the files of a table in its sorted binary table.
+/// # Arguments
+/// * `path` - The folder containing the rainbow table
+/// * `manifest` - The parameters of the table
+/// * `table` - The index of the table
+/// * `run_chains` - The number of chains sorted in memory at once
+/// * `perfect` - Only keep one chain per endpoint
+/// # Returns
+/// The number of chains read and the number of chains written.
+fn sort_table(path: &Path, manifest: &Manifest, table: u16, run_chains: usize, perfect: bool) -> std::io::Result<(u64, u64)> {
+    // Sort the table by runs
+    let mut runs = Vec::new();
+    let mut run = Vec::with_capacity(run_chains);
+    let mut read = 0;
+    for i in 0..manifest.files {
+        let file = table_path(path, table, i, manifest.format);
+        let mut reader = open_reader(&file, manifest.format, manifest.hash, &manifest.keyspace, run_chains)?;
+        while let Some(chunk) = reader.next_chunk()? {
+            read += chunk.len() as u64;
+            for chain in chunk {
+                run.push(chain);
+                if run.len() == run_chains {
+                    runs.push(write_run(path, runs.len(), &mut run, manifest, perfect)?);
+                }
+            }
+        }
+        report_discarded(&file, reader.as_ref());
+    }
+    if !run.is_empty() {
+        runs.push(write_run(path, runs.len(), &mut run, manifest, perfect)?);
+    }
+
+    // Merge the runs
+    let chains = merge_runs(&runs, &sorted_path(path, table), manifest, perfect)?;
+    for run in runs {
+        fs::remove_file(run)?;
+    }
+    Ok((read, chains))
+}
+
+/// Remove a file, if it exists.
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Sort a run of chains by endpoint and write it in a temporary file.
+/// # Arguments
+/// * `path` - The folder containing the rainbow table
+/// * `number` - The number of the run
+/// * `run` - The chains of the run, emptied by the function
+/// * `manifest` - The parameters of the table
+/// * `perfect` - Only keep one chain per endpoint
+/// # Returns
+/// The path of the temporary file.
+fn write_run(path: &Path, number: usize, run: &mut Vec<Chain>, manifest: &Manifest, perfect: bool) -> std::io::Result<PathBuf> {
+    let run_path = path.join(format!("run_{}.tmp", number));
+    if run_path.exists() {
+        fs::remove_file(&run_path)?;
+    }
+    run.sort_unstable_by_key(|chain| (chain.end, chain.start));
+    if perfect {
+        run.dedup_by_key(|chain| chain.end);
+    }
+    let mut writer = open_writer(&run_path, TableFormat::Binary, manifest.hash, &manifest.keyspace)?;
+    for chain in run.drain(..) {
+        writer.write_chain(&chain)?;
+    }
+    writer.sync()?;
+    Ok(run_path)
+}
+
+/// Merge sorted runs in a single sorted binary table.
+/// # Arguments
+/// * `runs` - The paths of the sorted runs
+/// * `output` - The path of the sorted table
+/// * `manifest` - The parameters of the table
+/// * `perfect` - Only keep one chain per endpoint
+/// # Returns
+/// The number of chains written.
+fn merge_runs(runs: &[PathBuf], output: &Path, manifest: &Manifest, perfect: bool) -> std::io::Result<u64> {
+    // Write in a temporary file so the previous sorted table stays valid until the end
+    let tmp = output.with_extension("tmp");
+    if tmp.exists() {
+        fs::remove_file(&tmp)?;
+    }
+    let mut writer = open_writer(&tmp, TableFormat::Binary, manifest.hash, &manifest.keyspace)?;
+
+    // Readers of the runs and their current chunk
+    let mut readers: Vec<(Box<dyn TableReader>, std::vec::IntoIter<Chain>)> = Vec::new();
+    let chunk = (RUN_CHAINS / runs.len().max(1)).clamp(1, 1 << 16);
+    for run in runs {
+        readers.push((open_reader(run, TableFormat::Binary, manifest.hash, &manifest.keyspace, chunk)?, Vec::new().into_iter()));
+    }
+
+    // Min-heap of the next chain of each run
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(chain) = next_chain(reader)? {
+            heap.push(Reverse((chain.end, chain.start, i)));
+        }
+    }
+
+    let mut chains = 0;
+    let mut last_end = None;
+    while let Some(Reverse((end, start, i))) = heap.pop() {
+        // The chains are popped by endpoint then start point: the first one of an endpoint is kept
+        if !perfect || last_end != Some(end) {
+            writer.write_chain(&Chain { start, end })?;
+            chains += 1;
+            last_end = Some(end);
+        }
+        if let Some(chain) = next_chain(&mut readers[i])? {
+            heap.push(Reverse((chain.end, chain.start, i)));
+        }
+    }
+    writer.sync()?;
+    drop(writer);
+    fs::rename(tmp, output)?;
+    Ok(chains)
+}
+
+/// Get the next chain of a run.
+fn next_chain(reader: &mut (Box<dyn TableReader>, std::vec::IntoIter<Chain>)) -> std::io::Result<Option<Chain>> {
+    if let Some(chain) = reader.1.next() {
+        return Ok(Some(chain));
+    }
+    match reader.0.next_chunk()? {
+        Some(chunk) => {
+            reader.1 = chunk.into_iter();
+            Ok(reader.1.next())
+        },
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::algorithm::HashType;
+    use crate::charset::Charset;
+    use crate::keyspace::Keyspace;
+    use crate::table::SortedTable;
+
+    #[test]
+    fn test_sort_main() {
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-sort");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // Two table files with unsorted endpoints
+        let keyspace = Arc::new(Keyspace::fixed(Arc::new(Charset::preset("base64").unwrap()), 3).unwrap());
+        let manifest = Manifest::new(TableFormat::Text, HashType::Sha3_256, 10, 0, keyspace, 1, 2);
+        manifest.write(&dir).unwrap();
+        for i in 0..2 {
+            let mut writer = open_writer(&table_path(&dir, 0, i, TableFormat::Text), TableFormat::Text, HashType::Sha3_256, &manifest.keyspace).unwrap();
+            for j in 0..50 {
+                writer.write_chain(&Chain { start: (i * 1000 + j) as u128, end: ((j * 37 + i * 11) % 64) as u128 }).unwrap();
+            }
+            writer.sync().unwrap();
+        }
+
+        // Small runs to test the merge
+        sort_main(dir.clone(), 7, false).unwrap();
+        assert!(Manifest::read(&dir).unwrap().unwrap().sorted);
+
+        let mut reader = open_reader(&dir.join(SORTED_FILE), TableFormat::Binary, HashType::Sha3_256, &manifest.keyspace, 1000).unwrap();
+        let chains = reader.next_chunk().unwrap().unwrap();
+        assert_eq!(chains.len(), 100);
+        assert!(chains.windows(2).all(|w| w[0].end <= w[1].end));
+
+        let mut table = SortedTable::open(&dir.join(SORTED_FILE), HashType::Sha3_256, &manifest.keyspace).unwrap();
+        assert_eq!(table.find(11).unwrap(), vec![47, 1000]);
+
+        // Perfect table: one chain per endpoint, the table files are removed
+        sort_main(dir.clone(), 7, true).unwrap();
+        let manifest = Manifest::read(&dir).unwrap().unwrap();
+        assert!(manifest.perfect);
+        assert_eq!(manifest.chains, vec![64]);
+        assert!(manifest.validate().is_ok());
+        assert!(!table_path(&dir, 0, 0, TableFormat::Text).exists());
+        let mut reader = open_reader(&dir.join(SORTED_FILE), TableFormat::Binary, HashType::Sha3_256, &manifest.keyspace, 1000).unwrap();
+        let chains = reader.next_chunk().unwrap().unwrap();
+        assert_eq!(chains.len(), 64);
+        assert!(chains.windows(2).all(|w| w[0].end < w[1].end));
+        let mut table = SortedTable::open(&dir.join(SORTED_FILE), HashType::Sha3_256, &manifest.keyspace).unwrap();
+        assert_eq!(table.find(11).unwrap(), vec![47]);
+
+        // Nothing left to sort
+        sort_main(dir.clone(), 7, false).unwrap();
+        assert!(Manifest::read(&dir).unwrap().unwrap().perfect);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
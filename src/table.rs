@@ -0,0 +1,774 @@
+//! Reading and writing of the rainbow table files produced by the generation.
+//!
+//! Two formats are available:
+//! * `text`: one `{start}{end}\n` line per chain (2 * password_length + 1 bytes per chain),
+//!   `{start}\t{end}\n` when the passwords have several lengths
+//! * `binary`: a header followed by fixed-width little-endian records,
+//!   the start point and the endpoint as keyspace indices packed on the smallest number of bytes (up to 16)
+//!
+//! The header of a binary table holds fingerprints of the charset (or mask) and of the hash algorithm,
+//! so a table file is never searched with the parameters of another table.
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use std::sync::Arc;
+
+use crate::algorithm::HashType;
+use crate::keyspace::Keyspace;
+
+/// Number of chains read at once by the search.
+pub const CHUNK_CHAINS: usize = 100_000;
+
+/// Size of the buffer of a table writer, in bytes.
+const WRITE_BUFFER: usize = 1 << 16;
+
+/// Magic bytes at the beginning of a binary table.
+const MAGIC: &[u8; 8] = b"AECTABLE";
+
+/// Version of the binary table format.
+const BINARY_VERSION: u32 = 1;
+
+/// Size of the header of a binary table.
+pub const HEADER_SIZE: u64 = 32;
+
+/// Name of the binary table sorted by endpoint (of the first table of the folder).
+pub const SORTED_FILE: &str = "sorted.bin";
+
+/// Format of the table files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum TableFormat {
+    /// One `{start}{end}` line per chain
+    Text,
+    /// Fixed-width binary records
+    Binary,
+}
+
+impl TableFormat {
+    /// Get the size of a chain in a table file.
+    /// # Arguments
+    /// * `keyspace` - The passwords of the table
+    /// # Returns
+    /// The number of bytes used by a chain, None if the records do not have a fixed size.
+    pub fn record_size(&self, keyspace: &Keyspace) -> Option<u64> {
+        match self {
+            TableFormat::Text if keyspace.is_fixed() => Some(2 * keyspace.max_length as u64 + 1),
+            TableFormat::Text => None,
+            TableFormat::Binary => Some(2 * index_bytes(keyspace) as u64),
+        }
+    }
+
+    /// Get the average size of a chain in a table file.
+    /// # Arguments
+    /// * `keyspace` - The passwords of the table
+    /// # Returns
+    /// The number of bytes used by a chain, on average for the text records of several lengths.
+    pub fn mean_record_size(&self, keyspace: &Keyspace) -> f64 {
+        match self.record_size(keyspace) {
+            Some(size) => size as f64,
+            // Two passwords, a tab and a newline
+            None => 2.0 * keyspace.mean_length() + 2.0,
+        }
+    }
+
+    /// Get the size of the header of a table file.
+    pub fn header_size(&self) -> u64 {
+        match self {
+            TableFormat::Text => 0,
+            TableFormat::Binary => HEADER_SIZE,
+        }
+    }
+}
+
+/// Display the format as in the command line.
+impl Display for TableFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TableFormat::Text => write!(f, "text"),
+            TableFormat::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// Parse the format as in the command line.
+impl FromStr for TableFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TableFormat::Text),
+            "binary" => Ok(TableFormat::Binary),
+            _ => Err(format!("Unknown table format: {}", s)),
+        }
+    }
+}
+
+/// A chain of the rainbow table.
+/// # Fields
+/// * `start` - The keyspace index of the first password
+/// * `end` - The keyspace index of the last password
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chain {
+    pub start: u128,
+    pub end: u128,
+}
+
+/// Number of bytes needed to store a keyspace index.
+/// # Arguments
+/// * `keyspace` - The passwords of the table
+fn index_bytes(keyspace: &Keyspace) -> usize {
+    let last = keyspace.size() - 1;
+    ((u128::BITS - last.leading_zeros()) as usize).div_ceil(8).max(1)
+}
+
+/// Get the path of the table file written by a thread.
+/// # Arguments
+/// * `path` - The folder containing the rainbow table
+/// * `table` - The index of the table in the folder
+/// * `thread` - The thread number
+/// * `format` - The format of the table
+/// # Returns
+/// The path of the table file.
+/// # Note
+/// The files of the first table keep the names of the folders holding a single table (`table_{thread}.bin`),
+/// the files of the other tables are prefixed by their index (`table_{table}_{thread}.bin`).
+pub fn table_path(path: &Path, table: u16, thread: u64, format: TableFormat) -> PathBuf {
+    let name = match table {
+        0 => thread.to_string(),
+        _ => format!("{}_{}", table, thread),
+    };
+    match format {
+        TableFormat::Text => path.join(format!("test_{}.txt", name)),
+        TableFormat::Binary => path.join(format!("table_{}.bin", name)),
+    }
+}
+
+/// Count the files of the first table of a folder, numbered from 0.
+/// # Arguments
+/// * `path` - The folder containing the rainbow table
+/// * `format` - The format of the table
+/// # Returns
+/// The number of files before the first missing one.
+pub fn count_files(path: &Path, format: TableFormat) -> u64 {
+    (0..).take_while(|&thread| table_path(path, 0, thread, format).exists()).count() as u64
+}
+
+/// Get the path of the sorted binary table of a table.
+/// # Arguments
+/// * `path` - The folder containing the rainbow table
+/// * `table` - The index of the table in the folder
+/// # Returns
+/// The path of `sorted.bin` for the first table, `sorted_{table}.bin` for the others.
+pub fn sorted_path(path: &Path, table: u16) -> PathBuf {
+    match table {
+        0 => path.join(SORTED_FILE),
+        _ => path.join(format!("sorted_{}.bin", table)),
+    }
+}
+
+/// Reader returning the chains of a table file chunk by chunk.
+pub trait TableReader: Send {
+    /// Read the next chunk of the file.
+    /// # Returns
+    /// The chains of the chunk, None once the end of the file is reached.
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<Chain>>>;
+
+    /// Get the number of records skipped so far: a truncated record at the end of the file, or an invalid line.
+    fn discarded(&self) -> u64;
+
+    /// Get the number of bytes of the file read so far.
+    fn bytes_read(&self) -> u64;
+}
+
+/// Report the records skipped by a reader.
+/// # Arguments
+/// * `path` - The path of the table file
+/// * `reader` - The reader of the table file, at the end of the file
+pub fn report_discarded(path: &Path, reader: &dyn TableReader) {
+    if reader.discarded() > 0 {
+        println!("{}: {} truncated or invalid records skipped", path.display(), reader.discarded());
+    }
+}
+
+/// Open a table file for reading.
+/// # Arguments
+/// * `path` - The path of the table file
+/// * `format` - The format of the table
+/// * `hash` - The hash algorithm of the table
+/// * `keyspace` - The passwords of the table
+/// * `chains_per_chunk` - The maximum number of chains in a chunk
+/// # Returns
+/// The reader of the table file.
+pub fn open_reader(path: &Path, format: TableFormat, hash: HashType, keyspace: &Arc<Keyspace>, chains_per_chunk: usize) -> io::Result<Box<dyn TableReader>> {
+    Ok(match format {
+        TableFormat::Text => Box::new(TextReader::open(path, keyspace, chains_per_chunk)?),
+        TableFormat::Binary => Box::new(BinaryReader::open(path, hash, keyspace, chains_per_chunk)?),
+    })
+}
+
+/// Writer appending chains to a table file.
+pub trait TableWriter: Send {
+    /// Append a chain to the table file.
+    fn write_chain(&mut self, chain: &Chain) -> io::Result<()>;
+
+    /// Flush the buffered chains and synchronize the file.
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+/// Buffer of whole records, so an interrupted write never leaves a partial record written before a complete one.
+/// # Fields
+/// * `file` - The table file, opened in append mode
+/// * `buffer` - The records not written yet
+/// # Note
+/// Unlike a `BufWriter`, the buffer is only written when it cannot hold the next record, never in the middle of a record.
+struct RecordBuffer {
+    file: File,
+    buffer: Vec<u8>,
+}
+
+impl RecordBuffer {
+    fn new(file: File) -> RecordBuffer {
+        RecordBuffer { file, buffer: Vec::with_capacity(WRITE_BUFFER) }
+    }
+
+    /// Append a record to the buffer, writing the previous records if it is full.
+    fn push(&mut self, record: &[u8]) -> io::Result<()> {
+        if self.buffer.len() + record.len() > WRITE_BUFFER {
+            self.flush()?;
+        }
+        self.buffer.extend_from_slice(record);
+        Ok(())
+    }
+
+    /// Write the buffered records in a single call.
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Write the buffered records and wait until they are on the disk.
+    fn sync(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.file.sync_all()
+    }
+}
+
+/// Write the records left when the writer is dropped without being synchronized.
+impl Drop for RecordBuffer {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Open a table file for appending chains, creating it if needed.
+/// # Arguments
+/// * `path` - The path of the table file
+/// * `format` - The format of the table
+/// * `hash` - The hash algorithm of the table
+/// * `keyspace` - The passwords of the table
+/// # Returns
+/// The writer of the table file.
+pub fn open_writer(path: &Path, format: TableFormat, hash: HashType, keyspace: &Arc<Keyspace>) -> io::Result<Box<dyn TableWriter>> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(match format {
+        TableFormat::Text => Box::new(TextWriter { file: RecordBuffer::new(file), keyspace: keyspace.clone() }),
+        TableFormat::Binary => {
+            if file.metadata()?.len() == 0 {
+                file.write_all(&binary_header(hash, keyspace))?;
+            }
+            Box::new(BinaryWriter { file: RecordBuffer::new(file), index_bytes: index_bytes(keyspace) })
+        },
+    })
+}
+
+/// Buffered reader of a text table.
+/// # Note
+/// A chunk only contains complete lines: a `start+end` record is never split between two chunks.
+/// A last line without its `\n` (partially written record) and invalid lines are ignored and counted.
+pub struct TextReader {
+    reader: BufReader<File>,
+    keyspace: Arc<Keyspace>,
+    chains_per_chunk: usize,
+    discarded: u64,
+    bytes_read: u64,
+}
+
+impl TextReader {
+    /// Open a text table file.
+    pub fn open(path: &Path, keyspace: &Arc<Keyspace>, chains_per_chunk: usize) -> io::Result<TextReader> {
+        Ok(TextReader {
+            reader: BufReader::new(File::open(path)?),
+            keyspace: keyspace.clone(),
+            chains_per_chunk,
+            discarded: 0,
+            bytes_read: 0,
+        })
+    }
+}
+
+impl TableReader for TextReader {
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<Chain>>> {
+        let mut chains = Vec::with_capacity(self.chains_per_chunk);
+        let mut buf = Vec::new();
+        while chains.len() < self.chains_per_chunk {
+            buf.clear();
+            if self.reader.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            self.bytes_read += buf.len() as u64;
+            // Partially written record at the end of the file
+            if buf.pop() != Some(b'\n') {
+                self.discarded += 1;
+                break;
+            }
+            let record = std::str::from_utf8(&buf).ok().and_then(|line| split_record(line, &self.keyspace));
+            match record.map(|(start, end)| (self.keyspace.index(start), self.keyspace.index(end))) {
+                Some((Some(start), Some(end))) => chains.push(Chain { start, end }),
+                _ => self.discarded += 1,
+            }
+        }
+        Ok(if chains.is_empty() { None } else { Some(chains) })
+    }
+
+    fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+}
+
+/// Buffered writer of a text table.
+struct TextWriter {
+    file: RecordBuffer,
+    keyspace: Arc<Keyspace>,
+}
+
+impl TableWriter for TextWriter {
+    fn write_chain(&mut self, chain: &Chain) -> io::Result<()> {
+        let start = self.keyspace.password(chain.start);
+        let end = self.keyspace.password(chain.end);
+        if self.keyspace.is_fixed() {
+            self.file.push(format!("{}{}\n", start, end).as_bytes())
+        } else {
+            self.file.push(format!("{}\t{}\n", start, end).as_bytes())
+        }
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync()
+    }
+}
+
+/// Buffered reader of a binary table.
+/// # Note
+/// A partially written record at the end of the file is ignored and counted.
+pub struct BinaryReader {
+    reader: BufReader<File>,
+    index_bytes: usize,
+    chains_per_chunk: usize,
+    discarded: u64,
+    bytes_read: u64,
+}
+
+impl BinaryReader {
+    /// Open a binary table file and check its header.
+    pub fn open(path: &Path, hash: HashType, keyspace: &Keyspace, chains_per_chunk: usize) -> io::Result<BinaryReader> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; HEADER_SIZE as usize];
+        reader.read_exact(&mut header)?;
+        check_header(path, &header, &binary_header(hash, keyspace))?;
+        Ok(BinaryReader {
+            reader,
+            index_bytes: index_bytes(keyspace),
+            chains_per_chunk,
+            discarded: 0,
+            bytes_read: HEADER_SIZE,
+        })
+    }
+}
+
+impl TableReader for BinaryReader {
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<Chain>>> {
+        let record_size = 2 * self.index_bytes;
+        let mut buf = vec![0; record_size * self.chains_per_chunk];
+        let mut len = 0;
+        // Fill the buffer until the end of the file
+        while len < buf.len() {
+            match self.reader.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        self.bytes_read += len as u64;
+        // The partial record at the end of the file is dropped
+        if len % record_size != 0 {
+            self.discarded += 1;
+        }
+        let chains: Vec<Chain> = buf[..len - len % record_size].chunks_exact(record_size).map(|record| {
+            let (start, end) = record.split_at(self.index_bytes);
+            Chain { start: read_index(start), end: read_index(end) }
+        }).collect();
+        Ok(if chains.is_empty() { None } else { Some(chains) })
+    }
+
+    fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+}
+
+/// Buffered writer of a binary table.
+struct BinaryWriter {
+    file: RecordBuffer,
+    index_bytes: usize,
+}
+
+impl TableWriter for BinaryWriter {
+    fn write_chain(&mut self, chain: &Chain) -> io::Result<()> {
+        // Two indexes of at most 16 bytes
+        let mut record = [0; 32];
+        record[..self.index_bytes].copy_from_slice(&chain.start.to_le_bytes()[..self.index_bytes]);
+        record[self.index_bytes..2 * self.index_bytes].copy_from_slice(&chain.end.to_le_bytes()[..self.index_bytes]);
+        self.file.push(&record[..2 * self.index_bytes])
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync()
+    }
+}
+
+/// Binary table sorted by endpoint, searched by binary search.
+/// # Fields
+/// * `file` - The table file
+/// * `index_bytes` - The number of bytes of an index
+/// * `len` - The number of chains in the table
+pub struct SortedTable {
+    file: File,
+    index_bytes: usize,
+    len: u64,
+}
+
+impl SortedTable {
+    /// Open a sorted binary table and check its header.
+    /// # Arguments
+    /// * `path` - The path of the table file
+    /// * `hash` - The hash algorithm of the table
+    /// * `keyspace` - The passwords of the table
+    pub fn open(path: &Path, hash: HashType, keyspace: &Keyspace) -> io::Result<SortedTable> {
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        check_header(path, &header, &binary_header(hash, keyspace))?;
+        let index_bytes = index_bytes(keyspace);
+        let len = (file.metadata()?.len() - HEADER_SIZE) / (2 * index_bytes) as u64;
+        Ok(SortedTable { file, index_bytes, len })
+    }
+
+    /// Read the chain at a position of the table.
+    fn chain(&mut self, position: u64) -> io::Result<Chain> {
+        let record_size = 2 * self.index_bytes;
+        let mut record = vec![0; record_size];
+        self.file.seek(SeekFrom::Start(HEADER_SIZE + position * record_size as u64))?;
+        self.file.read_exact(&mut record)?;
+        let (start, end) = record.split_at(self.index_bytes);
+        Ok(Chain { start: read_index(start), end: read_index(end) })
+    }
+
+    /// Find the start points of the chains ending with an endpoint.
+    /// # Arguments
+    /// * `end` - The keyspace index of the endpoint
+    /// # Returns
+    /// The keyspace indexes of the start points (O(log m) reads).
+    pub fn find(&mut self, end: u128) -> io::Result<Vec<u128>> {
+        // Lower bound of the endpoint
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.chain(middle)?.end < end {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        // Several chains can share the endpoint
+        let mut starts = Vec::new();
+        while low < self.len {
+            let chain = self.chain(low)?;
+            if chain.end != end {
+                break;
+            }
+            starts.push(chain.start);
+            low += 1;
+        }
+        Ok(starts)
+    }
+}
+
+/// Build the header of a binary table.
+/// # Arguments
+/// * `hash` - The hash algorithm of the table
+/// * `keyspace` - The passwords of the table
+/// # Returns
+/// The magic bytes, the format version (u32), the start and end sizes (u8), the minimum and maximum password lengths (u8),
+/// the fingerprint of the charset or of the mask (u64) and the fingerprint of the hash algorithm (u64).
+fn binary_header(hash: HashType, keyspace: &Keyspace) -> [u8; HEADER_SIZE as usize] {
+    let mut header = [0; HEADER_SIZE as usize];
+    header[0..8].copy_from_slice(MAGIC);
+    header[8..12].copy_from_slice(&BINARY_VERSION.to_le_bytes());
+    header[12] = index_bytes(keyspace) as u8;
+    header[13] = index_bytes(keyspace) as u8;
+    header[14] = keyspace.min_length as u8;
+    header[15] = keyspace.max_length as u8;
+    let charset = match &keyspace.mask {
+        Some(mask) => mask.to_string(),
+        None => keyspace.charset.to_string(),
+    };
+    header[16..24].copy_from_slice(&fingerprint(charset.as_bytes()).to_le_bytes());
+    header[24..32].copy_from_slice(&fingerprint(hash.to_string().as_bytes()).to_le_bytes());
+    header
+}
+
+/// Check the header of a binary table against the header expected from the manifest.
+/// # Arguments
+/// * `path` - The path of the table file
+/// * `header` - The header read from the file
+/// * `expected` - The header built from the parameters of the table
+/// # Returns
+/// An error describing the first parameter of the file which differs from the manifest.
+fn check_header(path: &Path, header: &[u8; HEADER_SIZE as usize], expected: &[u8; HEADER_SIZE as usize]) -> io::Result<()> {
+    let error = |message: &str| Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} {}", path.display(), message)));
+    if header[0..12] != expected[0..12] {
+        return error("is not a binary table of this version");
+    }
+    if header[12..16] != expected[12..16] {
+        return error(&format!("is not a binary table of {}-{} character passwords", expected[14], expected[15]));
+    }
+    if header[16..24] != expected[16..24] {
+        return error("was generated with another charset or mask than the manifest");
+    }
+    if header[24..32] != expected[24..32] {
+        return error("was generated with another hash algorithm than the manifest");
+    }
+    Ok(())
+}
+
+/// Fingerprint of a table parameter stored in the header (64-bit FNV-1a).
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Read a little-endian index stored on up to 16 bytes.
+fn read_index(bytes: &[u8]) -> u128 {
+    let mut buf = [0; 16];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    u128::from_le_bytes(buf)
+}
+
+/// Split a line of a text table into its start and end passwords.
+/// # Arguments
+/// * `line` - The line to split
+/// * `keyspace` - The passwords of the table
+/// # Returns
+/// The start and end passwords, None if the line is not a valid record.
+pub fn split_record<'a>(line: &'a str, keyspace: &Keyspace) -> Option<(&'a str, &'a str)> {
+    if !keyspace.is_fixed() {
+        return line.split_once('\t');
+    }
+    let password_length = keyspace.max_length;
+    if line.len() != 2 * password_length || !line.is_char_boundary(password_length) {
+        return None;
+    }
+    Some(line.split_at(password_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::charset::Charset;
+    use crate::mask::Mask;
+
+    /// The passwords of the tests with the default charset (`0-9 A-Z a-z ! *`).
+    fn keyspace(min_length: usize, max_length: usize) -> Arc<Keyspace> {
+        Arc::new(Keyspace::new(Arc::new(Charset::preset("base64").unwrap()), min_length, max_length).unwrap())
+    }
+
+    /// Write chains in a table and read them back chunk by chunk.
+    fn round_trip(format: TableFormat, keyspace: Arc<Keyspace>, name: &str) {
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = table_path(&dir, 0, 0, format);
+        let _ = std::fs::remove_file(&path);
+
+        let chains: Vec<Chain> = (0..25).map(|i| Chain { start: i * 70, end: (1 << 42) - 1 - i }).collect();
+        let mut writer = open_writer(&path, format, HashType::Sha3_256, &keyspace).unwrap();
+        chains.iter().for_each(|chain| writer.write_chain(chain).unwrap());
+        writer.sync().unwrap();
+        drop(writer);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), format.header_size() + 25 * format.record_size(&keyspace).unwrap());
+
+        // Partially written record
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"0025").unwrap();
+
+        let mut reader = open_reader(&path, format, HashType::Sha3_256, &keyspace, 10).unwrap();
+        let mut read = Vec::new();
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            read.push(chunk);
+        }
+        assert_eq!(read.iter().map(|c| c.len()).collect::<Vec<_>>(), vec![10, 10, 5]);
+        assert_eq!(read.concat(), chains);
+        assert_eq!(reader.discarded(), 1);
+        assert_eq!(reader.bytes_read(), std::fs::metadata(&path).unwrap().len());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        round_trip(TableFormat::Text, keyspace(7, 7), "arc-en-ciel-test-text");
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        round_trip(TableFormat::Binary, keyspace(7, 7), "arc-en-ciel-test-binary");
+    }
+
+    #[test]
+    fn test_long_round_trip() {
+        // 64^20 passwords: indexes of 15 bytes
+        assert_eq!(TableFormat::Binary.record_size(&keyspace(20, 20)), Some(30));
+        round_trip(TableFormat::Binary, keyspace(20, 20), "arc-en-ciel-test-binary-long");
+    }
+
+    #[test]
+    fn test_lengths_round_trip() {
+        // 64 + ... + 64^7 passwords, the indexes still fit on 6 bytes
+        round_trip(TableFormat::Binary, keyspace(1, 7), "arc-en-ciel-test-binary-lengths");
+
+        let keyspace = keyspace(1, 7);
+        assert_eq!(TableFormat::Text.record_size(&keyspace), None);
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-text-lengths");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = table_path(&dir, 0, 0, TableFormat::Text);
+        let _ = std::fs::remove_file(&path);
+        let chains: Vec<Chain> = (0..25).map(|i| Chain { start: i * 70, end: keyspace.size() - 1 - i }).collect();
+        let mut writer = open_writer(&path, TableFormat::Text, HashType::Sha3_256, &keyspace).unwrap();
+        chains.iter().for_each(|chain| writer.write_chain(chain).unwrap());
+        writer.sync().unwrap();
+        drop(writer);
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with("0\t*******\n"));
+        let mut reader = open_reader(&path, TableFormat::Text, HashType::Sha3_256, &keyspace, 100).unwrap();
+        assert_eq!(reader.next_chunk().unwrap().unwrap(), chains);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_binary_header() {
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-header");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = table_path(&dir, 0, 0, TableFormat::Binary);
+        std::fs::write(&path, binary_header(HashType::Sha3_256, &keyspace(7, 7))).unwrap();
+        assert!(open_reader(&path, TableFormat::Binary, HashType::Sha3_256, &keyspace(7, 7), 10).is_ok());
+        assert!(open_reader(&path, TableFormat::Binary, HashType::Sha3_256, &keyspace(6, 7), 10).is_err());
+
+        // Table of the same size with another charset
+        let legacy = Arc::new(Keyspace::fixed(Charset::legacy(), 7).unwrap());
+        let error = open_reader(&path, TableFormat::Binary, HashType::Sha3_256, &legacy, 10).err().unwrap();
+        assert!(error.to_string().contains("another charset"));
+
+        // Tables of two masks with the same characters
+        let mask = |mask: &str| Arc::new(Keyspace::from_mask(Mask::new(mask, &[]).unwrap()).unwrap());
+        std::fs::write(&path, binary_header(HashType::Sha3_256, &mask("?l?d"))).unwrap();
+        assert!(open_reader(&path, TableFormat::Binary, HashType::Sha3_256, &mask("?l?d"), 10).is_ok());
+        assert!(open_reader(&path, TableFormat::Binary, HashType::Sha3_256, &mask("?d?l"), 10).is_err());
+
+        // Table of another hash algorithm
+        std::fs::write(&path, binary_header(HashType::Md5, &keyspace(7, 7))).unwrap();
+        let error = open_reader(&path, TableFormat::Binary, HashType::Sha3_256, &keyspace(7, 7), 10).err().unwrap();
+        assert!(error.to_string().contains("another hash algorithm"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_record_atomic_writes() {
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-atomic");
+        std::fs::create_dir_all(&dir).unwrap();
+        // 7-byte text records do not divide the size of the buffer
+        let keyspace = keyspace(3, 3);
+        let path = table_path(&dir, 0, 0, TableFormat::Text);
+        let _ = std::fs::remove_file(&path);
+        let mut writer = open_writer(&path, TableFormat::Text, HashType::Sha3_256, &keyspace).unwrap();
+        for i in 0..20_000 {
+            writer.write_chain(&Chain { start: i, end: i }).unwrap();
+            // The buffer is only written between two records
+            assert_eq!(std::fs::metadata(&path).unwrap().len() % 7, 0);
+        }
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        writer.sync().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 7 * 20_000);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sorted_table_find() {
+        let dir = std::env::temp_dir().join("arc-en-ciel-test-sorted");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SORTED_FILE);
+        let _ = std::fs::remove_file(&path);
+
+        let keyspace = keyspace(4, 4);
+        let mut writer = open_writer(&path, TableFormat::Binary, HashType::Sha3_256, &keyspace).unwrap();
+        for end in [1, 3, 3, 3, 8, 10] {
+            writer.write_chain(&Chain { start: end * 100, end }).unwrap();
+        }
+        writer.sync().unwrap();
+        drop(writer);
+
+        let mut table = SortedTable::open(&path, HashType::Sha3_256, &keyspace).unwrap();
+        assert_eq!(table.len, 6);
+        assert_eq!(table.find(1).unwrap(), vec![100]);
+        assert_eq!(table.find(3).unwrap(), vec![300, 300, 300]);
+        assert_eq!(table.find(10).unwrap(), vec![1000]);
+        assert!(table.find(0).unwrap().is_empty());
+        assert!(table.find(5).unwrap().is_empty());
+        assert!(table.find(11).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_binary_is_smaller() {
+        assert_eq!(TableFormat::Text.record_size(&keyspace(7, 7)), Some(15));
+        assert_eq!(TableFormat::Binary.record_size(&keyspace(7, 7)), Some(12));
+        assert_eq!(TableFormat::Binary.record_size(&keyspace(4, 4)), Some(6));
+        // 10^7 - 1 < 2^24
+        assert_eq!(TableFormat::Binary.record_size(&Keyspace::fixed(Arc::new(Charset::preset("digits").unwrap()), 7).unwrap()), Some(6));
+    }
+
+    #[test]
+    fn test_table_paths() {
+        let dir = Path::new("tables");
+        assert_eq!(table_path(dir, 0, 3, TableFormat::Binary), dir.join("table_3.bin"));
+        assert_eq!(table_path(dir, 2, 3, TableFormat::Text), dir.join("test_2_3.txt"));
+        assert_eq!(sorted_path(dir, 0), dir.join(SORTED_FILE));
+        assert_eq!(sorted_path(dir, 1), dir.join("sorted_1.bin"));
+        assert_eq!(count_files(dir, TableFormat::Text), 0);
+    }
+
+    #[test]
+    fn test_split_record_invalid() {
+        assert_eq!(split_record("abc", &keyspace(2, 2)), None);
+        assert_eq!(split_record("abcd", &keyspace(2, 2)), Some(("ab", "cd")));
+        assert_eq!(split_record("a\tcd", &keyspace(1, 2)), Some(("a", "cd")));
+    }
+}